@@ -0,0 +1,48 @@
+use std::fs::read;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use csv::{ByteRecord, ReaderBuilder};
+use pixel_crab::rplace_data_parser::{RawRecord, Record, UserInterner};
+
+fn decode_records(c: &mut Criterion) {
+    let bytes =
+        read("assets/rplace_data_sample/2023_place_canvas_history-000000000000.csv").unwrap();
+
+    let mut group = c.benchmark_group("decode_records");
+
+    group.bench_function("deserialize", |b| {
+        b.iter(|| {
+            let mut reader = ReaderBuilder::new().from_reader(bytes.as_slice());
+
+            let mut count = 0;
+
+            for result in reader.deserialize::<RawRecord>() {
+                result.unwrap();
+                count += 1;
+            }
+
+            count
+        })
+    });
+
+    group.bench_function("from_byte_record", |b| {
+        b.iter(|| {
+            let mut reader = ReaderBuilder::new().from_reader(bytes.as_slice());
+            let mut byte_record = ByteRecord::new();
+            let mut users = UserInterner::new();
+            let mut count = 0;
+
+            while reader.read_byte_record(&mut byte_record).unwrap() {
+                Record::from_byte_record(&byte_record, &mut users).unwrap();
+                count += 1;
+            }
+
+            count
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decode_records);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use pixel_crab::{
+    image_io::ImageIO,
+    pixel_art_scanner::{Config, PixelArt},
+};
+
+fn search_in_image(c: &mut Criterion) {
+    let target_image =
+        ImageIO::load_rgb_image(&PathBuf::from("assets/images/crewmate.png")).unwrap();
+    let source_image =
+        ImageIO::load_rgb_image(&PathBuf::from("assets/images/final_2023_place.png")).unwrap();
+
+    let target_pixel_art = PixelArt::new(target_image, Config::new_default()).unwrap();
+
+    let mut group = c.benchmark_group("search_in_image");
+    group.sample_size(20);
+
+    group.bench_function("crewmate_final_2023_place", |b| {
+        b.iter(|| target_pixel_art.search_in_image(&source_image))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, search_in_image);
+criterion_main!(benches);
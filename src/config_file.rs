@@ -0,0 +1,271 @@
+use core::fmt;
+use std::{
+    fs::{create_dir_all, read_to_string, write},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+#[derive(Debug)]
+pub enum ConfigFileError {
+    UnsupportedExtension(String),
+    UnknownPreset(String),
+}
+
+impl fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigFileError::UnsupportedExtension(extension) => write!(
+                f,
+                "Unsupported config file extension {:?}, expected \"toml\" or \"json\"",
+                extension
+            ),
+            ConfigFileError::UnknownPreset(preset) => write!(f, "Unknown preset {:?}", preset),
+        }
+    }
+}
+
+impl std::error::Error for ConfigFileError {}
+
+/// Every problem found in a config, so that all of them can be fixed in one go.
+#[derive(Debug)]
+pub struct ValidationErrors<E> {
+    pub problems: Vec<E>,
+}
+
+impl<E> ValidationErrors<E> {
+    pub fn check(problems: Vec<E>) -> Result<(), ValidationErrors<E>> {
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        Err(ValidationErrors { problems })
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ValidationErrors<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid config, {} problem(s) found",
+            self.problems.len()
+        )?;
+
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: fmt::Display + fmt::Debug> std::error::Error for ValidationErrors<E> {}
+
+pub struct ConfigFile;
+
+impl ConfigFile {
+    /// Loads a TOML or JSON config. A `preset` key picks the preset the values of the file are
+    /// applied on top of, without it they are applied on top of the `default` preset.
+    pub fn load<T, F>(path: &PathBuf, preset: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: Fn(&str) -> Option<T>,
+    {
+        let content = read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        let parsed = match extension {
+            "toml" => toml::from_str::<toml::Value>(&content)
+                .map_err(anyhow::Error::from)
+                .and_then(|values| Ok(serde_json::to_value(values)?)),
+            "json" => serde_json::from_str(&content).map_err(anyhow::Error::from),
+            _ => Err(anyhow!(ConfigFileError::UnsupportedExtension(
+                extension.to_string()
+            ))),
+        };
+        let mut values: Value =
+            parsed.with_context(|| format!("Failed to parse config file {}", path.display()))?;
+
+        let preset_name = match values.as_object_mut().and_then(|map| map.remove("preset")) {
+            Some(Value::String(preset_name)) => preset_name,
+            Some(other) => {
+                return Err(anyhow!(ConfigFileError::UnknownPreset(other.to_string())))
+                    .with_context(|| format!("Invalid preset in config file {}", path.display()))
+            }
+            None => String::from("default"),
+        };
+
+        let preset = preset(&preset_name)
+            .ok_or_else(|| anyhow!(ConfigFileError::UnknownPreset(preset_name.clone())))
+            .with_context(|| format!("Invalid preset in config file {}", path.display()))?;
+        let mut merged = serde_json::to_value(preset)?;
+
+        if let (Some(merged), Some(values)) = (merged.as_object_mut(), values.as_object()) {
+            for (key, value) in values {
+                merged.insert(key.clone(), value.clone());
+            }
+        }
+
+        serde_json::from_value(merged)
+            .with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Writes the config as `effective_config.toml` into the directory, so that outputs can be
+    /// traced back to the settings that produced them.
+    pub fn save_effective<T: Serialize>(config: &T, directory: &str) -> Result<()> {
+        let directory_path = PathBuf::from(directory);
+
+        if !directory_path.exists() {
+            create_dir_all(&directory_path)?;
+        }
+
+        write(
+            directory_path.join("effective_config.toml"),
+            toml::to_string_pretty(config)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Serializes colors as `#RRGGBB` strings.
+pub mod hex_color {
+    use image::Rgb;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn to_hex(color: &Rgb<u8>) -> String {
+        let Rgb([r, g, b]) = color;
+
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    }
+
+    pub fn from_hex(s: &str) -> Result<Rgb<u8>, String> {
+        if s.len() != 7 || !s.starts_with('#') {
+            return Err(format!(
+                "Expected color in the #RRGGBB format but got {:?}",
+                s
+            ));
+        }
+
+        let channel = |range: std::ops::Range<usize>| {
+            u8::from_str_radix(&s[range], 16).map_err(|err| format!("{} in {:?}", err, s))
+        };
+
+        Ok(Rgb([channel(1..3)?, channel(3..5)?, channel(5..7)?]))
+    }
+
+    pub fn serialize<S: Serializer>(color: &Rgb<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_hex(color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rgb<u8>, D::Error> {
+        from_hex(&String::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+/// Serializes an optional list of colors as `#RRGGBB` strings.
+pub mod optional_hex_colors {
+    use image::Rgb;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::hex_color::{from_hex, to_hex};
+
+    pub fn serialize<S: Serializer>(
+        colors: &Option<Vec<Rgb<u8>>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        colors
+            .as_ref()
+            .map(|colors| colors.iter().map(to_hex).collect::<Vec<_>>())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<Rgb<u8>>>, D::Error> {
+        let Some(colors) = Option::<Vec<String>>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+
+        colors
+            .iter()
+            .map(|color| from_hex(color).map_err(de::Error::custom))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some)
+    }
+}
+
+/// Serializes an optional timestamp like `2023-07-20 13:00:26.088`, the fraction of a second
+/// can be left out.
+pub mod optional_timestamp {
+    use chrono::NaiveDateTime;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn parse(s: &str) -> Result<NaiveDateTime, chrono::ParseError> {
+        NaiveDateTime::parse_from_str(s, FORMAT)
+    }
+
+    pub fn serialize<S: Serializer>(
+        timestamp: &Option<NaiveDateTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        timestamp
+            .map(|timestamp| timestamp.format(FORMAT).to_string())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<NaiveDateTime>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|timestamp| parse(&timestamp).map_err(de::Error::custom))
+            .transpose()
+    }
+
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use image::Rgb;
+
+    use crate::{
+        pixel_art_scanner::{Config, Neighbourhood, Side},
+        rplace_data_parser::{OnError, ParserConfig},
+    };
+
+    #[test]
+    fn test_load_config_files() {
+        let config = Config::load(&PathBuf::from("assets/configs/scanner_lenient.toml")).unwrap();
+
+        assert_eq!(config.searching_similarity_tolerance, 8);
+        assert_eq!(config.searched_color, Rgb([1, 1, 1]));
+        assert_eq!(config.neighbourhood, Neighbourhood::Four);
+        assert_eq!(config.optional_sides, vec![Side::Bottom]);
+
+        let parser_config =
+            ParserConfig::load(&PathBuf::from("assets/configs/parser_strict.json")).unwrap();
+
+        assert!(matches!(parser_config.on_error, OnError::Stop));
+        assert_eq!(parser_config.save_interval_seconds, 3600);
+
+        let error = Config::load(&PathBuf::from("assets/configs/invalid_field.toml")).unwrap_err();
+
+        assert!(format!("{:#}", error).contains("unknown field `searching_tolerance`"));
+
+        let error = Config::load(&PathBuf::from("assets/configs/unknown_preset.toml")).unwrap_err();
+
+        assert!(format!("{:#}", error).contains("assets/configs/unknown_preset.toml"));
+        assert!(format!("{:#}", error).contains("Unknown preset \"sloppy\""));
+        assert!(Config::preset("sloppy").is_none());
+    }
+}
@@ -0,0 +1,267 @@
+use image::{Rgb, RgbImage};
+
+use crate::pixel_art_scanner::PixelArtMatch;
+
+pub struct Gradient {
+    stops: Vec<(f32, Rgb<u8>)>,
+}
+
+impl Gradient {
+    /// Stops are positions between 0 and 1 with the color at that position, sorted ascending.
+    pub fn new(stops: Vec<(f32, Rgb<u8>)>) -> Gradient {
+        Gradient { stops }
+    }
+
+    pub fn new_default() -> Gradient {
+        Gradient {
+            stops: vec![
+                (0.0, Rgb([0, 0, 0])),
+                (0.25, Rgb([32, 0, 160])),
+                (0.5, Rgb([220, 0, 60])),
+                (0.75, Rgb([255, 160, 0])),
+                (1.0, Rgb([255, 255, 255])),
+            ],
+        }
+    }
+
+    pub fn sample(&self, position: f32) -> Rgb<u8> {
+        let position = position.clamp(0.0, 1.0);
+
+        let Some(&(first_position, first_color)) = self.stops.first() else {
+            return Rgb([0, 0, 0]);
+        };
+
+        if position <= first_position {
+            return first_color;
+        }
+
+        for stops in self.stops.windows(2) {
+            let (start, start_color) = stops[0];
+            let (end, end_color) = stops[1];
+
+            if position <= end {
+                let t = if end > start {
+                    (position - start) / (end - start)
+                } else {
+                    1.0
+                };
+
+                let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+
+                return Rgb([
+                    mix(start_color[0], end_color[0]),
+                    mix(start_color[1], end_color[1]),
+                    mix(start_color[2], end_color[2]),
+                ]);
+            }
+        }
+
+        self.stops[self.stops.len() - 1].1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapScale {
+    Linear,
+    Logarithmic,
+}
+
+pub struct HeatmapConfig {
+    pub cell_size: u32,
+    pub gradient: Gradient,
+    pub scale: HeatmapScale,
+    pub legend: bool,
+}
+
+impl HeatmapConfig {
+    pub fn new(
+        cell_size: u32,
+        gradient: Gradient,
+        scale: HeatmapScale,
+        legend: bool,
+    ) -> HeatmapConfig {
+        HeatmapConfig {
+            cell_size,
+            gradient,
+            scale,
+            legend,
+        }
+    }
+
+    pub fn new_default() -> HeatmapConfig {
+        HeatmapConfig {
+            cell_size: 10,
+            gradient: Gradient::new_default(),
+            scale: HeatmapScale::Linear,
+            legend: true,
+        }
+    }
+}
+
+/// Counts binned into a grid of square cells, rendered as one pixel per cell.
+pub struct Heatmap {
+    config: HeatmapConfig,
+    width: u32,
+    height: u32,
+    values: Vec<u32>,
+    frames: u32,
+}
+
+impl Heatmap {
+    pub fn new(image_width: u32, image_height: u32, config: HeatmapConfig) -> Heatmap {
+        let cell_size = config.cell_size.max(1);
+        let width = image_width.div_ceil(cell_size);
+        let height = image_height.div_ceil(cell_size);
+
+        Heatmap {
+            config,
+            width,
+            height,
+            values: vec![0; (width * height) as usize],
+            frames: 0,
+        }
+    }
+
+    /// Wraps values that were already accumulated per cell, laid out row after row. Panics when
+    /// there isn't exactly one value per cell.
+    pub fn from_values(
+        width: u32,
+        height: u32,
+        values: Vec<u32>,
+        config: HeatmapConfig,
+    ) -> Heatmap {
+        assert_eq!(
+            values.len(),
+            width as usize * height as usize,
+            "Expected one value per cell of a {}x{} heatmap",
+            width,
+            height
+        );
+
+        Heatmap {
+            config,
+            width,
+            height,
+            values,
+            frames: 1,
+        }
+    }
+
+    pub fn values(&self) -> &[u32] {
+        &self.values
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn max_value(&self) -> u32 {
+        self.values.iter().copied().max().unwrap_or(0)
+    }
+
+    pub fn add_point(&mut self, x: u32, y: u32) {
+        let cell_size = self.config.cell_size.max(1);
+        let (cell_x, cell_y) = (x / cell_size, y / cell_size);
+
+        if cell_x < self.width && cell_y < self.height {
+            self.values[(cell_y * self.width + cell_x) as usize] += 1;
+        }
+    }
+
+    /// Adds the matches found in one frame, every match counted at the center of its bounding box.
+    pub fn add_frame(&mut self, matches: &[PixelArtMatch]) {
+        for instance in matches {
+            let ((min_x, min_y), (max_x, max_y)) = instance.bounding_box();
+
+            self.add_point((min_x + max_x) / 2, (min_y + max_y) / 2);
+        }
+
+        self.frames += 1;
+    }
+
+    pub fn render(&self) -> RgbImage {
+        let legend_height = if self.config.legend { LEGEND_HEIGHT } else { 0 };
+        let max_value = self.max_value();
+
+        let mut image = RgbImage::new(self.width, self.height + legend_height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let value = self.values[(y * self.width + x) as usize];
+
+                image.put_pixel(
+                    x,
+                    y,
+                    self.config
+                        .gradient
+                        .sample(self.normalize(value, max_value)),
+                );
+            }
+        }
+
+        // The legend runs from zero on the left to the highest value on the right
+        for y in 0..legend_height {
+            for x in 0..self.width {
+                let color = if y == 0 {
+                    LEGEND_SEPARATOR_COLOR
+                } else {
+                    self.config
+                        .gradient
+                        .sample(x as f32 / (self.width.max(2) - 1) as f32)
+                };
+
+                image.put_pixel(x, self.height + y, color);
+            }
+        }
+
+        image
+    }
+
+    fn normalize(&self, value: u32, max_value: u32) -> f32 {
+        if max_value == 0 {
+            return 0.0;
+        }
+
+        match self.config.scale {
+            HeatmapScale::Linear => value as f32 / max_value as f32,
+            HeatmapScale::Logarithmic => (value as f32).ln_1p() / (max_value as f32).ln_1p(),
+        }
+    }
+}
+
+const LEGEND_HEIGHT: u32 = 8;
+const LEGEND_SEPARATOR_COLOR: Rgb<u8> = Rgb([128, 128, 128]);
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use crate::pixel_art_scanner::PixelArtMatch;
+
+    use super::{Gradient, Heatmap, HeatmapConfig, HeatmapScale};
+
+    #[test]
+    fn test_render_heatmap() {
+        let gradient = Gradient::new(vec![(0.0, Rgb([0, 0, 0])), (1.0, Rgb([200, 100, 0]))]);
+        let config = HeatmapConfig::new(10, gradient, HeatmapScale::Linear, true);
+        let mut heatmap = Heatmap::new(25, 25, config);
+
+        let instance = |x: u32, y: u32| PixelArtMatch {
+            coordinates: vec![(x, y), (x + 1, y + 1)],
+            color: Rgb([0, 0, 0]),
+            score: 0,
+        };
+
+        heatmap.add_frame(&[instance(1, 1), instance(12, 1)]);
+        heatmap.add_frame(&[instance(2, 2), instance(24, 24)]);
+
+        let image = heatmap.render();
+
+        assert_eq!(heatmap.frames(), 2);
+        assert_eq!(heatmap.values(), &[2, 1, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(image.dimensions(), (3, 3 + 8));
+        assert_eq!(*image.get_pixel(0, 0), Rgb([200, 100, 0]));
+        assert_eq!(*image.get_pixel(1, 0), Rgb([100, 50, 0]));
+        assert_eq!(*image.get_pixel(1, 1), Rgb([0, 0, 0]));
+    }
+}
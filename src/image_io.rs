@@ -1,6 +1,11 @@
-use std::{fs::create_dir_all, path::PathBuf};
+use std::{
+    fs::{create_dir_all, File},
+    io::BufWriter,
+    path::PathBuf,
+};
 
-use image::{io::Reader as ImageReader, RgbImage};
+use image::{io::Reader as ImageReader, GrayImage, Rgb, RgbImage, RgbaImage};
+use png::{BitDepth, ColorType, Encoder};
 
 pub struct ImageIO {}
 
@@ -15,14 +20,51 @@ impl ImageIO {
         Ok(rgb_img)
     }
 
+    pub fn load_rgba_image(path: &PathBuf) -> Result<RgbaImage> {
+        let img = ImageReader::open(path)?.decode()?;
+
+        let rgba_img: RgbaImage = img.to_rgba8();
+
+        Ok(rgba_img)
+    }
+
     pub fn load_multiple_rgb_images(paths: &[PathBuf]) -> Result<Vec<RgbImage>> {
-        paths
-            .iter()
-            .map(|path| ImageIO::load_rgb_image(path))
-            .collect()
+        paths.iter().map(ImageIO::load_rgb_image).collect()
     }
 
     pub fn save_image(image: &RgbImage, path: &str, name: &str, extension: &str) -> Result<()> {
+        image.save(ImageIO::file_path(path, name, extension)?)?;
+
+        Ok(())
+    }
+
+    /// Saves an image of palette indices as an indexed PNG, one byte per pixel.
+    pub fn save_indexed_image(
+        indices: &GrayImage,
+        palette: &[Rgb<u8>],
+        path: &str,
+        name: &str,
+    ) -> Result<()> {
+        let file = File::create(ImageIO::file_path(path, name, ".png")?)?;
+
+        let mut encoder = Encoder::new(BufWriter::new(file), indices.width(), indices.height());
+        encoder.set_color(ColorType::Indexed);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.set_palette(
+            palette
+                .iter()
+                .flat_map(|color| color.0)
+                .collect::<Vec<u8>>(),
+        );
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(indices.as_raw())?;
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    fn file_path(path: &str, name: &str, extension: &str) -> Result<PathBuf> {
         let directory_path = PathBuf::from(path);
 
         if !directory_path.exists() {
@@ -33,8 +75,6 @@ impl ImageIO {
 
         path_to_file.push(format!("{}{}", name, extension));
 
-        image.save(path_to_file)?;
-
-        Ok(())
+        Ok(path_to_file)
     }
 }
@@ -0,0 +1,6 @@
+pub mod config_file;
+pub mod heatmap;
+pub mod image_io;
+pub mod pixel_art_scanner;
+pub mod row_reader;
+pub mod rplace_data_parser;
@@ -1,36 +1,71 @@
-use std::{path::PathBuf, time::Instant};
+use std::{path::PathBuf, sync::mpsc, thread, time::Instant};
 
+use chrono::NaiveDateTime;
 use image::Rgb;
-use pixel_art_scanner::Config;
-use rplace_data_parser::{Parser, ParserConfig};
-
-use crate::{image_io::ImageIO, pixel_art_scanner::PixelArt};
-
-mod image_io;
-mod pixel_art_scanner;
-mod rplace_data_parser;
+use pixel_crab::{
+    config_file::{hex_color::from_hex, optional_timestamp, ConfigFile},
+    heatmap::{Heatmap, HeatmapConfig},
+    image_io::ImageIO,
+    pixel_art_scanner::{
+        Area, CancellationToken, Config, MotifMiner, MotifMiningConfig, OverlapSuppressor,
+        OverlayConfig, OverlayRenderer, PixelArt, SearchOutcome, SuppressionConfig,
+        TemplateExtractor,
+    },
+    row_reader::PngRowReader,
+    rplace_data_parser::{
+        ActivityMap, ActivityMapConfig, AttributionTarget, AttributionTracker, BattleDetector,
+        BattleDetectorConfig, CanvasDiffTracker, ModerationLog, Parser, ParserConfig,
+        RecordExporter,
+    },
+};
 
 fn main() {
     let start_time = Instant::now();
 
-    // test_parser();
-    test_scan_image();
+    match std::env::args().nth(1).as_deref() {
+        Some("parse") => test_parser(&std::env::args().skip(2).collect::<Vec<_>>()),
+        Some("motifs") => test_mine_motifs(),
+        Some("stream") => test_scan_image_streaming(),
+        Some("tiled") => test_scan_image_tiled(),
+        Some("heatmap") => test_heatmap_over_time(),
+        Some("moderation") => test_moderation_log(),
+        Some("battles") => test_battle_detection(),
+        Some("diff") => test_canvas_diff(&std::env::args().skip(2).collect::<Vec<_>>()),
+        Some("attribution") => test_attribution(&std::env::args().skip(2).collect::<Vec<_>>()),
+        Some("activity") => test_activity_map(&std::env::args().skip(2).collect::<Vec<_>>()),
+        Some("extract") => extract_template(&std::env::args().skip(2).collect::<Vec<_>>()),
+        Some("scan") => test_scan_image(std::env::args().nth(2)),
+        _ => test_scan_image(None),
+    }
 
     let end_time = Instant::now();
     let elapsed_time = end_time - start_time;
     println!("Elapsed time: {:.2?}", elapsed_time);
 }
 
-fn test_scan_image() {
+/// Usage: scan [config file]
+fn test_scan_image(config_path: Option<String>) {
+    let config = match config_path {
+        Some(config_path) => Config::load(&PathBuf::from(config_path)).unwrap(),
+        None => Config::new_default(),
+    };
+    ConfigFile::save_effective(&config, "output/visualization").unwrap();
+
     let target_image =
         ImageIO::load_rgb_image(&PathBuf::from("assets/images/crewmate.png")).unwrap();
+    config.validate_with_template(&target_image).unwrap();
+
     let source_image =
         ImageIO::load_rgb_image(&PathBuf::from("assets/images/final_2023_place.png")).unwrap();
 
-    let target_pixel_art = PixelArt::new(target_image, Config::new_default()).unwrap();
+    let target_pixel_art = PixelArt::new(target_image, config).unwrap();
 
     let found_instances = target_pixel_art.search_in_image(&source_image);
 
+    let suppression_result =
+        OverlapSuppressor::new(SuppressionConfig::new_default()).suppress(found_instances);
+    let found_instances = suppression_result.kept;
+
     let visualization = PixelArt::visualize_pixel_arts(
         &source_image,
         &found_instances,
@@ -46,10 +81,499 @@ fn test_scan_image() {
     )
     .unwrap();
 
+    let overlay = OverlayRenderer::new(OverlayConfig::new_default())
+        .render(&source_image, &[&found_instances]);
+
+    ImageIO::save_image(&overlay, "output/visualization", "crewmate_overlay", ".png").unwrap();
+
+    let (img_width, img_height) = source_image.dimensions();
+    let mut heatmap = Heatmap::new(img_width, img_height, HeatmapConfig::new_default());
+    heatmap.add_frame(&found_instances);
+
+    ImageIO::save_image(
+        &heatmap.render(),
+        "output/visualization",
+        "crewmate_heatmap",
+        ".png",
+    )
+    .unwrap();
+
+    println!(
+        "{:?} ({} overlapping suppressed)",
+        found_instances.len(),
+        suppression_result.suppressed
+    );
+}
+
+const EXTRACT_USAGE: &str =
+    "Usage: extract <canvas> <left> <top> <width> <height> <seed x> <seed y> <template name>";
+
+/// Usage: extract <canvas> <left> <top> <width> <height> <seed x> <seed y> <template name>
+fn extract_template(args: &[String]) {
+    let [canvas_path, left, top, width, height, seed_x, seed_y, name] = args else {
+        eprintln!("{}", EXTRACT_USAGE);
+        return;
+    };
+
+    let number = |value: &String| {
+        value
+            .parse::<u32>()
+            .map_err(|err| format!("Invalid number {:?}: {}", value, err))
+    };
+    let arguments = || -> Result<(Area, (u32, u32)), String> {
+        let area = Area {
+            left: number(left)?,
+            top: number(top)?,
+            width: number(width)?,
+            height: number(height)?,
+        };
+
+        Ok((area, (number(seed_x)?, number(seed_y)?)))
+    };
+
+    let (area, seed) = match arguments() {
+        Ok(arguments) => arguments,
+        Err(err) => {
+            eprintln!("{}\n{}", err, EXTRACT_USAGE);
+            return;
+        }
+    };
+
+    let config = Config::new_default();
+    let template = ImageIO::load_rgb_image(&PathBuf::from(canvas_path))
+        .and_then(|canvas| TemplateExtractor::new(config.clone()).extract(&canvas, area, seed));
+    let template = match template {
+        Ok(template) => template,
+        Err(err) => {
+            eprintln!("Could not extract template: {}", err);
+            return;
+        }
+    };
+
+    ImageIO::save_image(
+        &template.to_image(&config, &Rgb([255, 255, 255])),
+        "output/templates",
+        name,
+        ".png",
+    )
+    .unwrap();
+    std::fs::write(
+        format!("output/templates/{}.txt", name),
+        template.to_text_template(&config).to_string(),
+    )
+    .unwrap();
+
+    println!("Extracted {} pixels", template.shape.len());
+}
+
+fn test_heatmap_over_time() {
+    let target_image =
+        ImageIO::load_rgb_image(&PathBuf::from("assets/images/crewmate.png")).unwrap();
+    let target_pixel_art = PixelArt::new(target_image, Config::new_default()).unwrap();
+
+    let mut frame_paths: Vec<(u32, PathBuf)> = std::fs::read_dir("output/output_images")
+        .unwrap()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let seconds = path.file_stem()?.to_str()?.parse::<u32>().ok()?;
+
+            Some((seconds, path))
+        })
+        .collect();
+    frame_paths.sort();
+
+    let Some((_, last_frame_path)) = frame_paths.last() else {
+        return;
+    };
+
+    // Frames taken before the last expansion of the canvas have their origin shifted
+    let (img_width, img_height) = image::image_dimensions(last_frame_path).unwrap();
+    let mut heatmap = Heatmap::new(img_width, img_height, HeatmapConfig::new_default());
+
+    for (seconds, path) in &frame_paths {
+        if image::image_dimensions(path).unwrap() != (img_width, img_height) {
+            continue;
+        }
+
+        let frame = ImageIO::load_rgb_image(path).unwrap();
+        let found_instances = target_pixel_art.search_in_image(&frame);
+        heatmap.add_frame(&found_instances);
+
+        println!("{}s: {} instances", seconds, found_instances.len());
+    }
+
+    ImageIO::save_image(
+        &heatmap.render(),
+        "output/visualization",
+        "crewmate_heatmap_over_time",
+        ".png",
+    )
+    .unwrap();
+}
+
+fn test_scan_image_tiled() {
+    let target_image =
+        ImageIO::load_rgb_image(&PathBuf::from("assets/images/crewmate.png")).unwrap();
+    let mut row_reader =
+        PngRowReader::open(&PathBuf::from("assets/images/final_2023_place.png")).unwrap();
+
+    let target_pixel_art = PixelArt::new(target_image, Config::new_default()).unwrap();
+
+    let found_instances = target_pixel_art
+        .search_in_image_tiled(&mut row_reader, 64)
+        .unwrap();
+
     println!("{:?}", found_instances.len());
 }
 
-fn test_parser() {
+fn test_scan_image_streaming() {
+    let target_image =
+        ImageIO::load_rgb_image(&PathBuf::from("assets/images/crewmate.png")).unwrap();
+    let source_image =
+        ImageIO::load_rgb_image(&PathBuf::from("assets/images/final_2023_place.png")).unwrap();
+
+    let target_pixel_art = PixelArt::new(target_image, Config::new_default()).unwrap();
+
+    let cancellation_token = CancellationToken::new();
+    let start_time = Instant::now();
+    let (sender, receiver) = mpsc::channel();
+
+    let outcome = thread::scope(|scope| {
+        // The sender moves into the search, so the receiver stops once the search returns
+        let search = scope.spawn(|| {
+            target_pixel_art.search_in_image_streaming(
+                &source_image,
+                move |found| sender.send(found).unwrap(),
+                &|rows_done: u32, rows_total: u32| {
+                    if rows_done.is_multiple_of(250) {
+                        let elapsed = start_time.elapsed();
+                        let eta =
+                            elapsed.mul_f64((rows_total - rows_done) as f64 / rows_done as f64);
+                        println!("{}/{} rows, ETA {:.2?}", rows_done, rows_total, eta);
+                    }
+                },
+                &cancellation_token,
+            )
+        });
+
+        for (index, found) in receiver.iter().enumerate() {
+            println!("Found instance #{} at {:?}", index, found.coordinates[0]);
+
+            if index + 1 >= 100 {
+                cancellation_token.cancel();
+                break;
+            }
+        }
+
+        search.join().unwrap()
+    });
+
+    if outcome == SearchOutcome::Cancelled {
+        println!("Search cancelled after 100 instances");
+    }
+}
+
+fn test_mine_motifs() {
+    let source_image =
+        ImageIO::load_rgb_image(&PathBuf::from("assets/images/final_2023_place.png")).unwrap();
+
+    let miner = MotifMiner::new(Config::new_default(), MotifMiningConfig::new_default());
+    let motifs = miner.mine(&source_image);
+
+    for (rank, motif) in motifs.iter().take(20).enumerate() {
+        ImageIO::save_image(
+            &motif.example,
+            "output/motifs",
+            &format!("{}_example", rank),
+            ".png",
+        )
+        .unwrap();
+        ImageIO::save_image(
+            &motif.to_template(&Config::new_default(), &Rgb([255, 255, 255])),
+            "output/motifs",
+            &format!("{}_template", rank),
+            ".png",
+        )
+        .unwrap();
+
+        println!(
+            "#{}: {} occurrences, {} pixels",
+            rank,
+            motif.count(),
+            motif.shape.len()
+        );
+    }
+}
+
+const PARSE_USAGE: &str = "Usage: parse [config file] [--from <timestamp>] [--to <timestamp>] \
+    [--users <file>] [--deny-users <file>] [--bbox <x1,y1,x2,y2>] [--colors <#RRGGBB,...>] \
+    [--shapes <point,...>] [--export <csv file>]";
+
+/// Usage: parse [config file] [--from <timestamp>] [--to <timestamp>] [--users <file>]
+/// [--deny-users <file>] [--bbox <x1,y1,x2,y2>] [--colors <#RRGGBB,...>] [--shapes <point,...>]
+/// [--export <csv file>]
+fn test_parser(args: &[String]) {
+    let paths = history_paths();
+
+    let (config_path, flags) = match args.first().filter(|arg| !arg.starts_with("--")) {
+        Some(config_path) => (Some(config_path), &args[1..]),
+        None => (None, args),
+    };
+
+    let mut config = match config_path {
+        Some(config_path) => match ParserConfig::load(&PathBuf::from(config_path)) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("Could not load {}: {}", config_path, err);
+                return;
+            }
+        },
+        None => ParserConfig::new_default(),
+    };
+
+    let export_path = match apply_parse_flags(&mut config, flags) {
+        Ok(export_path) => export_path,
+        Err(err) => {
+            eprintln!("{}\n{}", err, PARSE_USAGE);
+            return;
+        }
+    };
+
+    let mut parser = Parser::new(config);
+
+    match export_path {
+        Some(export_path) => {
+            let mut exporter = RecordExporter::create(&export_path).unwrap();
+            parser.parse_with(&paths, &mut [&mut exporter]).unwrap();
+            exporter.close().unwrap();
+        }
+        None => parser.parse(&paths).unwrap(),
+    }
+}
+
+/// Applies the flags of the parse subcommand to the filter of the config, returning the path to
+/// export records to if there is one.
+fn apply_parse_flags(
+    config: &mut ParserConfig,
+    flags: &[String],
+) -> Result<Option<PathBuf>, String> {
+    let mut export_path = None;
+    let mut flags = flags.iter().peekable();
+
+    while let Some(flag) = flags.next() {
+        let value = flags.next_if(|value| !value.starts_with("--"));
+        let value = || value.ok_or(format!("{} expects a value", flag));
+        let filter = &mut config.filter;
+
+        match flag.as_str() {
+            "--from" | "--to" => {
+                let value = value()?;
+                let timestamp = optional_timestamp::parse(value).map_err(|err| {
+                    format!("Invalid timestamp {:?} for {}: {}", value, flag, err)
+                })?;
+
+                if flag == "--from" {
+                    filter.start = Some(timestamp);
+                } else {
+                    filter.end = Some(timestamp);
+                }
+            }
+            "--users" => filter.allowed_users_file = Some(value()?.clone()),
+            "--deny-users" => filter.denied_users_file = Some(value()?.clone()),
+            "--bbox" => {
+                let value = value()?;
+                let numbers: Option<Vec<i32>> =
+                    list(value).map(|number| number.parse().ok()).collect();
+
+                filter.bounding_box = Some(
+                    numbers
+                        .and_then(|numbers| numbers.try_into().ok())
+                        .ok_or(format!("--bbox expects x1,y1,x2,y2 but got {:?}", value))?,
+                );
+            }
+            "--colors" => {
+                let colors = list(value()?).map(from_hex);
+
+                filter.colors = Some(colors.collect::<Result<_, _>>()?);
+            }
+            "--shapes" => {
+                let shapes = list(value()?).map(|shape| {
+                    serde_json::from_value(shape.into()).map_err(|_| {
+                        format!(
+                            "--shapes expects point, rectangle or circle but got {:?}",
+                            shape
+                        )
+                    })
+                });
+
+                filter.shapes = Some(shapes.collect::<Result<_, _>>()?);
+            }
+            "--export" => export_path = Some(PathBuf::from(value()?)),
+            flag => return Err(format!("Unknown flag {}", flag)),
+        }
+    }
+
+    Ok(export_path)
+}
+
+fn list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim)
+}
+
+fn test_moderation_log() {
+    let mut moderation_log = ModerationLog::new(10);
+    let mut parser = Parser::new(ParserConfig::new_default());
+
+    parser
+        .parse_with(&history_paths(), &mut [&mut moderation_log])
+        .unwrap();
+
+    moderation_log.save("output/moderation").unwrap();
+    parser
+        .users()
+        .save(&PathBuf::from("output/moderation/users.csv"))
+        .unwrap();
+
+    println!("{} moderation events", moderation_log.events().len());
+}
+
+const ACTIVITY_USAGE: &str =
+    "Usage: activity [start] [end], with timestamps like \"2023-07-20 13:00:00\"";
+
+/// Usage: activity [start] [end], with timestamps like "2023-07-20 13:00:00"
+fn test_activity_map(args: &[String]) {
+    if args.len() > 2 {
+        eprintln!("{}", ACTIVITY_USAGE);
+        return;
+    }
+
+    let bounds: Result<Vec<_>, _> = args.iter().map(|value| timestamp(value)).collect();
+    let (start, end) = match bounds {
+        Ok(bounds) => (bounds.first().copied(), bounds.get(1).copied()),
+        Err(err) => {
+            eprintln!("{}\n{}", err, ACTIVITY_USAGE);
+            return;
+        }
+    };
+
+    let mut activity_map = ActivityMap::new(ActivityMapConfig::new(start, end));
+    let mut parser = Parser::new(ParserConfig::new_default());
+
+    parser
+        .parse_with(&history_paths(), &mut [&mut activity_map])
+        .unwrap();
+
+    activity_map.save("output/activity").unwrap();
+}
+
+fn test_battle_detection() {
+    let mut battle_detector = BattleDetector::new(BattleDetectorConfig::new_default());
+    let mut parser = Parser::new(ParserConfig::new_default());
+
+    parser
+        .parse_with(&history_paths(), &mut [&mut battle_detector])
+        .unwrap();
+
+    battle_detector.save("output/battles").unwrap();
+
+    println!("{} battles", battle_detector.battles().len());
+}
+
+const ATTRIBUTION_USAGE: &str = "Usage: attribution <x1> <y1> <x2> <y2> <timestamp>, \
+    with a timestamp like \"2023-07-21 12:00:00\"";
+
+/// Usage: attribution <x1> <y1> <x2> <y2> <timestamp>, with a timestamp like "2023-07-21 12:00:00"
+fn test_attribution(args: &[String]) {
+    let [x1, y1, x2, y2, at] = args else {
+        eprintln!("{}", ATTRIBUTION_USAGE);
+        return;
+    };
+
+    let number = |value: &String| {
+        value
+            .parse::<i32>()
+            .map_err(|err| format!("Invalid number {:?}: {}", value, err))
+    };
+    let arguments = || -> Result<(AttributionTarget, NaiveDateTime), String> {
+        let target = AttributionTarget::from_rectangle(
+            (number(x1)?, number(y1)?),
+            (number(x2)?, number(y2)?),
+        );
+
+        Ok((target, timestamp(at)?))
+    };
+
+    let (target, at) = match arguments() {
+        Ok(arguments) => arguments,
+        Err(err) => {
+            eprintln!("{}\n{}", err, ATTRIBUTION_USAGE);
+            return;
+        }
+    };
+
+    let mut tracker = AttributionTracker::new(target, None, at);
+    let mut parser = Parser::new(ParserConfig::new_default());
+
+    parser
+        .parse_with(&history_paths(), &mut [&mut tracker])
+        .unwrap();
+
+    let attribution = tracker.attribution();
+    let users = parser.users();
+
+    for contributor in attribution.contributors.iter().take(20) {
+        println!(
+            "{} surviving pixels by {}",
+            contributor.surviving_pixels,
+            users.hash(contributor.user)
+        );
+    }
+    for destroyer in attribution.destroyers.iter().take(20) {
+        println!(
+            "{} attacks by {}",
+            destroyer.attacks,
+            users.hash(destroyer.user)
+        );
+    }
+}
+
+const DIFF_USAGE: &str = "Usage: diff <start> <end>, with timestamps like \"2023-07-21 12:00:00\"";
+
+/// Usage: diff <start> <end>, with timestamps like "2023-07-21 12:00:00"
+fn test_canvas_diff(args: &[String]) {
+    let [start, end] = args else {
+        eprintln!("{}", DIFF_USAGE);
+        return;
+    };
+
+    let (start, end) = match timestamp(start).and_then(|start| Ok((start, timestamp(end)?))) {
+        Ok(bounds) => bounds,
+        Err(err) => {
+            eprintln!("{}\n{}", err, DIFF_USAGE);
+            return;
+        }
+    };
+
+    let mut tracker = CanvasDiffTracker::new(start, end);
+    let mut parser = Parser::new(ParserConfig::new_default());
+
+    parser
+        .parse_with(&history_paths(), &mut [&mut tracker])
+        .unwrap();
+
+    let diff = tracker.diff().unwrap();
+    diff.save("output/diff").unwrap();
+
+    println!("{} pixels changed", diff.changes.len());
+}
+
+/// Parses timestamps given on the command line, like "2023-07-21 12:00:00".
+fn timestamp(value: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .map_err(|err| format!("Invalid timestamp {:?}: {}", value, err))
+}
+
+fn history_paths() -> Vec<PathBuf> {
     let mut paths = Vec::new();
 
     for i in 0..=52 {
@@ -57,7 +581,5 @@ fn test_parser() {
         paths.push(PathBuf::from(filename));
     }
 
-    let mut parser = Parser::new(ParserConfig::new_default());
-
-    parser.parse(&paths).unwrap();
+    paths
 }
@@ -13,6 +13,15 @@ impl ColorUtils {
 
         diff_r <= tolerance && diff_g <= tolerance && diff_b <= tolerance
     }
+
+    /// Largest difference between the channels of both colors, the smallest tolerance under
+    /// which they would be considered equal.
+    pub fn distance(color1: &Rgb<u8>, color2: &Rgb<u8>) -> u8 {
+        let Rgb([r1, g1, b1]) = color1;
+        let Rgb([r2, g2, b2]) = color2;
+
+        safe_abs(r1, r2).max(safe_abs(g1, g2)).max(safe_abs(b1, b2))
+    }
 }
 
 fn safe_abs(num1: &u8, num2: &u8) -> u8 {
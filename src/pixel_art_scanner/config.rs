@@ -1,10 +1,29 @@
-use image::Rgb;
+use core::fmt;
+use std::path::PathBuf;
 
+use anyhow::Result;
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use crate::config_file::{hex_color, ConfigFile, ValidationErrors};
+
+use super::{
+    color_utils::ColorUtils,
+    neighbourhood::{Neighbourhood, Side},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub extracting_tolerance: u8,
     pub searching_similarity_tolerance: u8,
     pub searching_contrast_tolerance: u8,
+    #[serde(with = "hex_color")]
     pub searched_color: Rgb<u8>,
+    #[serde(with = "hex_color")]
+    pub border_marker_color: Rgb<u8>,
+    pub neighbourhood: Neighbourhood,
+    pub optional_sides: Vec<Side>,
 }
 
 impl Config {
@@ -13,12 +32,18 @@ impl Config {
         searching_similarity_tolerance: u8,
         searching_contrast_tolerance: u8,
         searched_color: Rgb<u8>,
+        border_marker_color: Rgb<u8>,
+        neighbourhood: Neighbourhood,
+        optional_sides: Vec<Side>,
     ) -> Config {
         Config {
             extracting_tolerance,
             searching_similarity_tolerance,
             searching_contrast_tolerance,
             searched_color,
+            border_marker_color,
+            neighbourhood,
+            optional_sides,
         }
     }
 
@@ -28,6 +53,266 @@ impl Config {
             searching_similarity_tolerance: 1,
             searching_contrast_tolerance: 1,
             searched_color: Rgb([1, 1, 1]),
+            border_marker_color: Rgb([255, 0, 255]),
+            neighbourhood: Neighbourhood::Eight,
+            optional_sides: vec![],
+        }
+    }
+
+    /// Named presets: `default`, `strict` which only accepts perfectly uniform bodies with a
+    /// clearly different border, and `lenient` which tolerates noisy bodies.
+    pub fn preset(name: &str) -> Option<Config> {
+        match name {
+            "default" => Some(Config::new_default()),
+            "strict" => Some(Config {
+                extracting_tolerance: 0,
+                searching_similarity_tolerance: 0,
+                searching_contrast_tolerance: 16,
+                ..Config::new_default()
+            }),
+            "lenient" => Some(Config {
+                extracting_tolerance: 4,
+                searching_similarity_tolerance: 8,
+                searching_contrast_tolerance: 8,
+                ..Config::new_default()
+            }),
+            _ => None,
         }
     }
+
+    pub fn load(path: &PathBuf) -> Result<Config> {
+        let config: Config = ConfigFile::load(path, Config::preset)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationErrors<ConfigError>> {
+        ValidationErrors::check(self.problems())
+    }
+
+    /// Also checks that the template contains the searched color, which can be done as soon as
+    /// the template is loaded instead of after the searched image is loaded as well.
+    pub fn validate_with_template(
+        &self,
+        template: &RgbImage,
+    ) -> Result<(), ValidationErrors<ConfigError>> {
+        let mut problems = self.problems();
+
+        let contains_searched_color = template.pixels().any(|pixel| {
+            ColorUtils::equal_with_tolerance(pixel, &self.searched_color, self.extracting_tolerance)
+        });
+
+        if !contains_searched_color {
+            problems.push(ConfigError::SearchedColorNotInTemplate(self.searched_color));
+        }
+
+        ValidationErrors::check(problems)
+    }
+
+    fn problems(&self) -> Vec<ConfigError> {
+        let mut problems = vec![];
+
+        if ColorUtils::equal_with_tolerance(
+            &self.searched_color,
+            &self.border_marker_color,
+            self.extracting_tolerance,
+        ) {
+            problems.push(ConfigError::SearchedColorIsBorderMarker);
+        }
+
+        if self.searching_contrast_tolerance < self.searching_similarity_tolerance {
+            problems.push(ConfigError::ContrastBelowSimilarity {
+                similarity: self.searching_similarity_tolerance,
+                contrast: self.searching_contrast_tolerance,
+            });
+        }
+
+        if self.neighbourhood == Neighbourhood::Ring(0) {
+            problems.push(ConfigError::EmptyRing);
+        }
+
+        for (index, side) in self.optional_sides.iter().enumerate() {
+            if self.optional_sides[..index].contains(side) {
+                problems.push(ConfigError::DuplicateOptionalSide(*side));
+            }
+        }
+
+        problems
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    SearchedColorIsBorderMarker,
+    ContrastBelowSimilarity { similarity: u8, contrast: u8 },
+    EmptyRing,
+    DuplicateOptionalSide(Side),
+    SearchedColorNotInTemplate(Rgb<u8>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::SearchedColorIsBorderMarker => write!(
+                f,
+                "searched_color can't be told apart from border_marker_color"
+            ),
+            ConfigError::ContrastBelowSimilarity {
+                similarity,
+                contrast,
+            } => write!(
+                f,
+                "searching_contrast_tolerance ({}) is lower than searching_similarity_tolerance ({}), \
+                 so pixels similar to the body would count as contrasting",
+                contrast, similarity
+            ),
+            ConfigError::EmptyRing => write!(f, "neighbourhood ring of width 0 has no pixels"),
+            ConfigError::DuplicateOptionalSide(side) => {
+                write!(f, "optional side {:?} is listed more than once", side)
+            }
+            ConfigError::SearchedColorNotInTemplate(Rgb([r, g, b])) => write!(
+                f,
+                "searched_color #{:02X}{:02X}{:02X} doesn't appear in the template",
+                r, g, b
+            ),
+        }
+    }
+}
+
+pub struct MotifMiningConfig {
+    pub min_pixels: usize,
+    pub max_pixels: usize,
+    pub max_width: u32,
+    pub max_height: u32,
+    pub example_padding: u32,
+}
+
+impl MotifMiningConfig {
+    pub fn new(
+        min_pixels: usize,
+        max_pixels: usize,
+        max_width: u32,
+        max_height: u32,
+        example_padding: u32,
+    ) -> MotifMiningConfig {
+        MotifMiningConfig {
+            min_pixels,
+            max_pixels,
+            max_width,
+            max_height,
+            example_padding,
+        }
+    }
+
+    pub fn new_default() -> MotifMiningConfig {
+        MotifMiningConfig {
+            min_pixels: 5,
+            max_pixels: 64,
+            max_width: 12,
+            max_height: 12,
+            example_padding: 2,
+        }
+    }
+}
+
+pub struct SuppressionConfig {
+    pub allow_shared_pixels: bool,
+    pub max_overlap: f32,
+}
+
+impl SuppressionConfig {
+    pub fn new(allow_shared_pixels: bool, max_overlap: f32) -> SuppressionConfig {
+        SuppressionConfig {
+            allow_shared_pixels,
+            max_overlap,
+        }
+    }
+
+    pub fn new_default() -> SuppressionConfig {
+        SuppressionConfig {
+            allow_shared_pixels: false,
+            max_overlap: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayColorMode {
+    ByTemplate,
+    ByFillColor,
+}
+
+pub struct OverlayConfig {
+    pub dim_factor: f32,
+    pub draw_bounding_boxes: bool,
+    pub color_mode: OverlayColorMode,
+    pub scale: u32,
+    pub grid_color: Option<Rgb<u8>>,
+}
+
+impl OverlayConfig {
+    pub fn new(
+        dim_factor: f32,
+        draw_bounding_boxes: bool,
+        color_mode: OverlayColorMode,
+        scale: u32,
+        grid_color: Option<Rgb<u8>>,
+    ) -> OverlayConfig {
+        OverlayConfig {
+            dim_factor,
+            draw_bounding_boxes,
+            color_mode,
+            scale,
+            grid_color,
+        }
+    }
+
+    pub fn new_default() -> OverlayConfig {
+        OverlayConfig {
+            dim_factor: 0.3,
+            draw_bounding_boxes: true,
+            color_mode: OverlayColorMode::ByTemplate,
+            scale: 1,
+            grid_color: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::{Config, ConfigError, Neighbourhood, Side};
+
+    #[test]
+    fn test_validate_config() {
+        assert!(Config::new_default().validate().is_ok());
+
+        let config = Config {
+            searching_similarity_tolerance: 4,
+            searching_contrast_tolerance: 2,
+            border_marker_color: Rgb([1, 1, 1]),
+            neighbourhood: Neighbourhood::Ring(0),
+            optional_sides: vec![Side::Top, Side::Top],
+            ..Config::new_default()
+        };
+        let template = RgbImage::from_pixel(3, 3, Rgb([255, 255, 255]));
+
+        let errors = config.validate_with_template(&template).unwrap_err();
+
+        assert_eq!(
+            errors.problems,
+            vec![
+                ConfigError::SearchedColorIsBorderMarker,
+                ConfigError::ContrastBelowSimilarity {
+                    similarity: 4,
+                    contrast: 2
+                },
+                ConfigError::EmptyRing,
+                ConfigError::DuplicateOptionalSide(Side::Top),
+                ConfigError::SearchedColorNotInTemplate(Rgb([1, 1, 1])),
+            ]
+        );
+    }
 }
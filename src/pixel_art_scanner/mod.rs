@@ -1,6 +1,23 @@
-pub use config::Config;
-pub use pixel_art::PixelArt;
+pub use config::{
+    Config, ConfigError, MotifMiningConfig, OverlayColorMode, OverlayConfig, SuppressionConfig,
+};
+pub use motif_miner::{Motif, MotifMiner};
+pub use neighbourhood::{Neighbourhood, Side};
+pub use overlay::OverlayRenderer;
+pub use pixel_art::{PixelArt, PixelArtMatch};
+pub use search_control::{CancellationToken, ProgressObserver, SearchOutcome};
+pub use suppression::{OverlapSuppressor, SuppressionResult};
+pub use template_extractor::{Area, ExtractedTemplate, TemplateExtractor, TemplateExtractorError};
+pub use text_template::{TextTemplate, TextTemplateError};
 
 mod color_utils;
 mod config;
+mod motif_miner;
+mod neighbourhood;
+mod overlay;
+mod packed_canvas;
 mod pixel_art;
+mod search_control;
+mod suppression;
+mod template_extractor;
+mod text_template;
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use image::{imageops, Rgb, RgbImage};
+
+use super::{
+    color_utils::ColorUtils,
+    config::{Config, MotifMiningConfig},
+};
+
+pub struct Motif {
+    pub shape: Vec<(u32, u32)>,
+    pub occurrences: Vec<Vec<(u32, u32)>>,
+    pub example: RgbImage,
+}
+
+impl Motif {
+    pub fn count(&self) -> usize {
+        self.occurrences.len()
+    }
+
+    pub fn to_template(&self, config: &Config, background_color: &Rgb<u8>) -> RgbImage {
+        let (width, height) = shape_size(&self.shape);
+
+        let mut template = RgbImage::from_pixel(width, height, *background_color);
+
+        for &(x, y) in &self.shape {
+            template.put_pixel(x, y, config.searched_color);
+        }
+
+        template
+    }
+}
+
+pub struct MotifMiner {
+    config: Config,
+    mining_config: MotifMiningConfig,
+}
+
+impl MotifMiner {
+    pub fn new(config: Config, mining_config: MotifMiningConfig) -> MotifMiner {
+        MotifMiner {
+            config,
+            mining_config,
+        }
+    }
+
+    pub fn mine(&self, image: &RgbImage) -> Vec<Motif> {
+        let (img_width, img_height) = image.dimensions();
+
+        let mut visited = vec![false; (img_width * img_height) as usize];
+        let mut groups: HashMap<Vec<(u32, u32)>, Vec<_>> = HashMap::new();
+
+        for y in 0..img_height {
+            for x in 0..img_width {
+                if visited[(y * img_width + x) as usize] {
+                    continue;
+                }
+
+                let Some(region) = self.extract_region(image, &mut visited, x, y) else {
+                    continue;
+                };
+
+                if !self.region_is_isolated(image, &region) {
+                    continue;
+                }
+
+                groups
+                    .entry(canonical_shape(&region))
+                    .or_default()
+                    .push(region);
+            }
+        }
+
+        let mut motifs: Vec<Motif> = groups
+            .into_iter()
+            .map(|(shape, occurrences)| {
+                let example = self.crop_example(image, &occurrences[0]);
+
+                Motif {
+                    shape,
+                    occurrences,
+                    example,
+                }
+            })
+            .collect();
+
+        motifs.sort_by(|a, b| {
+            b.count()
+                .cmp(&a.count())
+                .then(b.shape.len().cmp(&a.shape.len()))
+                .then(a.shape.cmp(&b.shape))
+        });
+
+        motifs
+    }
+
+    /// Flood fills the uniform region around the seed and marks it as visited. Returns `None`
+    /// when the region does not fit the size limits of the mining config.
+    fn extract_region(
+        &self,
+        image: &RgbImage,
+        visited: &mut [bool],
+        seed_x: u32,
+        seed_y: u32,
+    ) -> Option<Vec<(u32, u32)>> {
+        let (img_width, img_height) = image.dimensions();
+        let seed_color = image.get_pixel(seed_x, seed_y);
+
+        let mut region = Vec::new();
+        let mut region_size = 0;
+        let mut stack = vec![(seed_x, seed_y)];
+        visited[(seed_y * img_width + seed_x) as usize] = true;
+
+        let connectivity_offsets = self.config.neighbourhood.connectivity_offsets();
+
+        while let Some((x, y)) = stack.pop() {
+            region_size += 1;
+
+            if region_size <= self.mining_config.max_pixels {
+                region.push((x, y));
+            }
+
+            for &(offset_x, offset_y) in &connectivity_offsets {
+                let x_with_offset = x as i32 + offset_x;
+                let y_with_offset = y as i32 + offset_y;
+
+                if x_with_offset < 0
+                    || y_with_offset < 0
+                    || x_with_offset >= img_width as i32
+                    || y_with_offset >= img_height as i32
+                {
+                    continue;
+                }
+
+                let (neighbour_x, neighbour_y) = (x_with_offset as u32, y_with_offset as u32);
+                let index = (neighbour_y * img_width + neighbour_x) as usize;
+
+                if visited[index] {
+                    continue;
+                }
+
+                if ColorUtils::equal_with_tolerance(
+                    seed_color,
+                    image.get_pixel(neighbour_x, neighbour_y),
+                    self.config.searching_similarity_tolerance,
+                ) {
+                    visited[index] = true;
+                    stack.push((neighbour_x, neighbour_y));
+                }
+            }
+        }
+
+        if region_size < self.mining_config.min_pixels
+            || region_size > self.mining_config.max_pixels
+        {
+            return None;
+        }
+
+        let (width, height) = shape_size(&normalize(&region));
+
+        if width > self.mining_config.max_width || height > self.mining_config.max_height {
+            return None;
+        }
+
+        region.sort_by_key(|&(x, y)| (y, x));
+
+        Some(region)
+    }
+
+    fn region_is_isolated(&self, image: &RgbImage, region: &[(u32, u32)]) -> bool {
+        let region_color = image.get_pixel(region[0].0, region[0].1);
+        let border_offsets = self.config.neighbourhood.offsets();
+
+        for &(x, y) in region {
+            for &(offset_x, offset_y) in &border_offsets {
+                let x_with_offset = x as i32 + offset_x;
+                let y_with_offset = y as i32 + offset_y;
+
+                if x_with_offset < 0 || y_with_offset < 0 {
+                    continue;
+                }
+
+                let neighbour = (x_with_offset as u32, y_with_offset as u32);
+
+                if region.contains(&neighbour) {
+                    continue;
+                }
+
+                if let Some(neighbour_color) = image.get_pixel_checked(neighbour.0, neighbour.1) {
+                    if ColorUtils::equal_with_tolerance(
+                        region_color,
+                        neighbour_color,
+                        self.config.searching_contrast_tolerance,
+                    ) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    fn crop_example(&self, image: &RgbImage, region: &[(u32, u32)]) -> RgbImage {
+        let (img_width, img_height) = image.dimensions();
+        let padding = self.mining_config.example_padding;
+
+        let min_x = region.iter().map(|&(x, _)| x).min().unwrap_or(0);
+        let min_y = region.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        let max_x = region.iter().map(|&(x, _)| x).max().unwrap_or(0);
+        let max_y = region.iter().map(|&(_, y)| y).max().unwrap_or(0);
+
+        let left = min_x.saturating_sub(padding);
+        let top = min_y.saturating_sub(padding);
+        let right = (max_x + padding).min(img_width - 1);
+        let bottom = (max_y + padding).min(img_height - 1);
+
+        imageops::crop_imm(image, left, top, right - left + 1, bottom - top + 1).to_image()
+    }
+}
+
+/// Returns the representative of the shape among all of its rotations and mirror images, so that
+/// identical shapes end up with identical keys regardless of orientation.
+fn canonical_shape(region: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let points: Vec<(i32, i32)> = region.iter().map(|&(x, y)| (x as i32, y as i32)).collect();
+
+    SYMMETRIES
+        .iter()
+        .map(|transform| {
+            let transformed: Vec<(i32, i32)> = points.iter().map(|&p| transform(p)).collect();
+
+            normalize(&transformed)
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn normalize<T: Copy + Into<i64>>(points: &[(T, T)]) -> Vec<(u32, u32)> {
+    let min_x = points.iter().map(|&(x, _)| x.into()).min().unwrap_or(0);
+    let min_y = points.iter().map(|&(_, y)| y.into()).min().unwrap_or(0);
+
+    let mut normalized: Vec<(u32, u32)> = points
+        .iter()
+        .map(|&(x, y)| ((x.into() - min_x) as u32, (y.into() - min_y) as u32))
+        .collect();
+
+    normalized.sort_by_key(|&(x, y)| (y, x));
+
+    normalized
+}
+
+fn shape_size(shape: &[(u32, u32)]) -> (u32, u32) {
+    let width = shape.iter().map(|&(x, _)| x + 1).max().unwrap_or(0);
+    let height = shape.iter().map(|&(_, y)| y + 1).max().unwrap_or(0);
+
+    (width, height)
+}
+
+type Symmetry = fn((i32, i32)) -> (i32, i32);
+
+const SYMMETRIES: [Symmetry; 8] = [
+    |(x, y)| (x, y),
+    |(x, y)| (-y, x),
+    |(x, y)| (-x, -y),
+    |(x, y)| (y, -x),
+    |(x, y)| (-x, y),
+    |(x, y)| (y, x),
+    |(x, y)| (x, -y),
+    |(x, y)| (-y, -x),
+];
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use image::Rgb;
+
+    use crate::{
+        image_io::ImageIO,
+        pixel_art_scanner::{Config, MotifMiningConfig},
+    };
+
+    use super::{canonical_shape, MotifMiner};
+
+    #[test]
+    fn test_mine_crewmates() {
+        let image =
+            ImageIO::load_rgb_image(&PathBuf::from("assets/images/8_crewmates.png")).unwrap();
+        let crewmate =
+            ImageIO::load_rgb_image(&PathBuf::from("assets/images/crewmate.png")).unwrap();
+
+        let crewmate_shape: Vec<(u32, u32)> = crewmate
+            .enumerate_pixels()
+            .filter(|(_, _, color)| **color == Rgb([0, 0, 0]))
+            .map(|(x, y, _)| (x, y))
+            .collect();
+
+        let miner = MotifMiner::new(Config::new_default(), MotifMiningConfig::new_default());
+        let motifs = miner.mine(&image);
+
+        assert_eq!(motifs[0].count(), 8);
+        assert_eq!(motifs[0].shape, canonical_shape(&crewmate_shape));
+    }
+}
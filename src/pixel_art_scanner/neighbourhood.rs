@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Neighbourhood {
+    Four,
+    Eight,
+    /// Every pixel within the given chessboard distance, so a ring of width N around the body.
+    Ring(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Neighbourhood {
+    pub fn offsets(&self) -> Vec<(i32, i32)> {
+        match self {
+            Neighbourhood::Four => ORTHOGONAL_OFFSETS.to_vec(),
+            Neighbourhood::Eight => SURROUNDING_OFFSETS.to_vec(),
+            Neighbourhood::Ring(width) => {
+                let width = *width as i32;
+                let mut offsets = vec![];
+
+                for offset_y in -width..=width {
+                    for offset_x in -width..=width {
+                        if offset_x != CENTER || offset_y != CENTER {
+                            offsets.push((offset_x, offset_y));
+                        }
+                    }
+                }
+
+                offsets
+            }
+        }
+    }
+
+    /// Offsets under which two pixels of the same color belong to the same region. Diagonal
+    /// neighbours only join a region when the border itself reaches diagonally.
+    pub fn connectivity_offsets(&self) -> Vec<(i32, i32)> {
+        match self {
+            Neighbourhood::Four => ORTHOGONAL_OFFSETS.to_vec(),
+            Neighbourhood::Eight | Neighbourhood::Ring(_) => SURROUNDING_OFFSETS.to_vec(),
+        }
+    }
+}
+
+impl Side {
+    /// Sides of the body bounding box the coordinate lies beyond. Coordinates in the corners are
+    /// on two sides at once, coordinates inside the bounding box on none.
+    pub fn sides_of(
+        (x, y): (i32, i32),
+        (min_x, min_y): (i32, i32),
+        (max_x, max_y): (i32, i32),
+    ) -> Vec<Side> {
+        let mut sides = vec![];
+
+        if y < min_y {
+            sides.push(Side::Top);
+        }
+        if y > max_y {
+            sides.push(Side::Bottom);
+        }
+        if x < min_x {
+            sides.push(Side::Left);
+        }
+        if x > max_x {
+            sides.push(Side::Right);
+        }
+
+        sides
+    }
+}
+
+const ORTHOGONAL_OFFSETS: [(i32, i32); 4] = [
+    (CENTER, TOP),
+    (LEFT, CENTER),
+    (RIGHT, CENTER),
+    (CENTER, BOTTOM),
+];
+
+const SURROUNDING_OFFSETS: [(i32, i32); 8] = [
+    (LEFT, TOP),
+    (CENTER, TOP),
+    (RIGHT, TOP),
+    (LEFT, CENTER),
+    (RIGHT, CENTER),
+    (LEFT, BOTTOM),
+    (CENTER, BOTTOM),
+    (RIGHT, BOTTOM),
+];
+
+const LEFT: i32 = -1;
+const RIGHT: i32 = 1;
+const TOP: i32 = -1;
+const BOTTOM: i32 = 1;
+const CENTER: i32 = 0;
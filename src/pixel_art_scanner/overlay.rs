@@ -0,0 +1,143 @@
+use image::{imageops, Rgb, RgbImage};
+
+use super::{
+    config::{OverlayColorMode, OverlayConfig},
+    pixel_art::PixelArtMatch,
+};
+
+pub struct OverlayRenderer {
+    config: OverlayConfig,
+}
+
+impl OverlayRenderer {
+    pub fn new(config: OverlayConfig) -> OverlayRenderer {
+        OverlayRenderer { config }
+    }
+
+    /// Draws the matches on top of the canvas they were found in. Every group holds the matches
+    /// of one template and decides the box color when coloring by template.
+    pub fn render(&self, original_image: &RgbImage, match_groups: &[&[PixelArtMatch]]) -> RgbImage {
+        let mut overlay = original_image.clone();
+
+        for pixel in overlay.pixels_mut() {
+            let Rgb([r, g, b]) = *pixel;
+
+            *pixel = Rgb([r, g, b].map(|channel| (channel as f32 * self.config.dim_factor) as u8));
+        }
+
+        for group in match_groups {
+            for instance in group.iter() {
+                for &(x, y) in &instance.coordinates {
+                    overlay.put_pixel(x, y, *original_image.get_pixel(x, y));
+                }
+            }
+        }
+
+        let scale = self.config.scale.max(1);
+        let (img_width, img_height) = original_image.dimensions();
+
+        if scale > 1 {
+            overlay = imageops::resize(
+                &overlay,
+                img_width * scale,
+                img_height * scale,
+                imageops::FilterType::Nearest,
+            );
+        }
+
+        if let (Some(grid_color), true) = (self.config.grid_color, scale > 1) {
+            for (x, y, pixel) in overlay.enumerate_pixels_mut() {
+                if x % scale == 0 || y % scale == 0 {
+                    *pixel = grid_color;
+                }
+            }
+        }
+
+        if self.config.draw_bounding_boxes {
+            for (template_index, group) in match_groups.iter().enumerate() {
+                for instance in group.iter() {
+                    let box_color = match self.config.color_mode {
+                        OverlayColorMode::ByTemplate => {
+                            TEMPLATE_COLORS[template_index % TEMPLATE_COLORS.len()]
+                        }
+                        OverlayColorMode::ByFillColor => instance.color,
+                    };
+
+                    draw_bounding_box(&mut overlay, instance, scale, &box_color);
+                }
+            }
+        }
+
+        overlay
+    }
+}
+
+/// Outlines the instance with a rectangle running just outside of its pixels.
+fn draw_bounding_box(image: &mut RgbImage, instance: &PixelArtMatch, scale: u32, color: &Rgb<u8>) {
+    let ((min_x, min_y), (max_x, max_y)) = instance.bounding_box();
+    let (img_width, img_height) = image.dimensions();
+
+    let left = (min_x * scale) as i64 - 1;
+    let top = (min_y * scale) as i64 - 1;
+    let right = ((max_x + 1) * scale) as i64;
+    let bottom = ((max_y + 1) * scale) as i64;
+
+    let mut put_pixel = |x: i64, y: i64| {
+        if x >= 0 && y >= 0 && x < img_width as i64 && y < img_height as i64 {
+            image.put_pixel(x as u32, y as u32, *color);
+        }
+    };
+
+    for x in left..=right {
+        put_pixel(x, top);
+        put_pixel(x, bottom);
+    }
+    for y in top..=bottom {
+        put_pixel(left, y);
+        put_pixel(right, y);
+    }
+}
+
+const TEMPLATE_COLORS: [Rgb<u8>; 6] = [
+    Rgb([255, 0, 0]),
+    Rgb([0, 255, 0]),
+    Rgb([0, 128, 255]),
+    Rgb([255, 255, 0]),
+    Rgb([255, 0, 255]),
+    Rgb([0, 255, 255]),
+];
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use crate::pixel_art_scanner::{OverlayColorMode, OverlayConfig, PixelArtMatch};
+
+    use super::OverlayRenderer;
+
+    #[test]
+    fn test_render_overlay() {
+        let original_image = RgbImage::from_pixel(6, 6, Rgb([200, 100, 50]));
+        let instance = PixelArtMatch {
+            coordinates: vec![(2, 2), (3, 2)],
+            color: Rgb([200, 100, 50]),
+            score: 255,
+        };
+
+        let renderer = OverlayRenderer::new(OverlayConfig::new(
+            0.5,
+            true,
+            OverlayColorMode::ByTemplate,
+            2,
+            Some(Rgb([1, 2, 3])),
+        ));
+        let overlay = renderer.render(&original_image, &[&[], &[instance]]);
+
+        assert_eq!(overlay.dimensions(), (12, 12));
+        assert_eq!(*overlay.get_pixel(1, 1), Rgb([100, 50, 25]));
+        assert_eq!(*overlay.get_pixel(5, 5), Rgb([200, 100, 50]));
+        assert_eq!(*overlay.get_pixel(4, 4), Rgb([1, 2, 3]));
+        assert_eq!(*overlay.get_pixel(3, 3), Rgb([0, 255, 0]));
+        assert_eq!(*overlay.get_pixel(8, 6), Rgb([0, 255, 0]));
+    }
+}
@@ -0,0 +1,143 @@
+use image::{Rgb, RgbImage};
+
+use super::color_utils::ColorUtils;
+
+/// Canvas with every pixel packed into a single `u32` laid out row after row, so that a window
+/// can be matched through precomputed flat index offsets instead of per pixel lookups.
+///
+/// It can also hold just a strip of consecutive rows of a bigger image, in which case indexes
+/// are still computed from image coordinates but only rows of the strip can be read.
+pub struct PackedCanvas {
+    pixels: Vec<u32>,
+    width: u32,
+    height: u32,
+    first_row: u32,
+}
+
+impl PackedCanvas {
+    pub fn from_image(image: &RgbImage) -> PackedCanvas {
+        let (width, height) = image.dimensions();
+
+        let pixels = image
+            .as_raw()
+            .chunks_exact(3)
+            .map(|rgb| pack(rgb[0], rgb[1], rgb[2]))
+            .collect();
+
+        PackedCanvas {
+            pixels,
+            width,
+            height,
+            first_row: 0,
+        }
+    }
+
+    pub fn new_strip(width: u32, height: u32) -> PackedCanvas {
+        PackedCanvas {
+            pixels: vec![],
+            width,
+            height,
+            first_row: 0,
+        }
+    }
+
+    /// Rows of the image currently held, the end being exclusive.
+    pub fn rows(&self) -> (u32, u32) {
+        let row_count = self.pixels.len() as u32 / self.width.max(1);
+
+        (self.first_row, self.first_row + row_count)
+    }
+
+    pub fn push_row(&mut self, rgb_row: &[u8]) {
+        self.pixels.extend(
+            rgb_row
+                .chunks_exact(3)
+                .map(|rgb| pack(rgb[0], rgb[1], rgb[2])),
+        );
+    }
+
+    pub fn drop_rows_before(&mut self, row: u32) {
+        let (first_row, last_row) = self.rows();
+        let dropped_rows = row.clamp(first_row, last_row) - first_row;
+
+        self.pixels
+            .drain(..dropped_rows as usize * self.width as usize);
+        self.first_row += dropped_rows;
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn get(&self, index: usize) -> u32 {
+        self.pixels[index]
+    }
+
+    /// Panics when the row isn't held.
+    pub fn index_of(&self, x: u32, y: u32) -> usize {
+        assert!(self.holds_row(y), "Row {} is outside of the rows held", y);
+
+        (y - self.first_row) as usize * self.width as usize + x as usize
+    }
+
+    /// Pixels outside of the image or of the rows held are `None`.
+    pub fn get_checked(&self, x: i32, y: i32) -> Option<u32> {
+        if x < 0 || y < 0 || x >= self.width as i32 || !self.holds_row(y as u32) {
+            return None;
+        }
+
+        Some(self.pixels[self.index_of(x as u32, y as u32)])
+    }
+
+    fn holds_row(&self, y: u32) -> bool {
+        y >= self.first_row
+            && (y - self.first_row + 1) as usize * self.width as usize <= self.pixels.len()
+    }
+}
+
+pub fn pack(r: u8, g: u8, b: u8) -> u32 {
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+pub fn unpack(color: u32) -> Rgb<u8> {
+    Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8])
+}
+
+/// [`ColorUtils::distance`] of two packed colors.
+pub fn packed_distance(color1: u32, color2: u32) -> u8 {
+    ColorUtils::distance(&unpack(color1), &unpack(color2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pack, PackedCanvas};
+
+    #[test]
+    fn test_strip_only_reads_rows_held() {
+        let mut canvas = PackedCanvas::new_strip(2, 4);
+
+        for row in 0..3 {
+            canvas.push_row(&[row, 0, 0, row, 0, 1]);
+        }
+        canvas.drop_rows_before(1);
+
+        assert_eq!(canvas.rows(), (1, 3));
+        assert_eq!(canvas.get_checked(1, 2), Some(pack(2, 0, 1)));
+        assert_eq!(canvas.get(canvas.index_of(0, 1)), pack(1, 0, 0));
+        assert_eq!(canvas.get_checked(0, 0), None);
+        assert_eq!(canvas.get_checked(0, 3), None);
+        assert_eq!(canvas.get_checked(-1, 1), None);
+        assert_eq!(canvas.get_checked(2, 1), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_of_row_before_strip() {
+        let mut canvas = PackedCanvas::new_strip(2, 4);
+
+        canvas.push_row(&[0; 6]);
+        canvas.push_row(&[0; 6]);
+        canvas.drop_rows_before(1);
+        canvas.index_of(0, 0);
+    }
+}
@@ -1,16 +1,74 @@
 use core::fmt;
-use std::collections::HashSet;
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use anyhow::{anyhow, Result};
-use image::{ImageBuffer, Rgb, RgbImage};
+use image::{ImageBuffer, Rgb, RgbImage, RgbaImage};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use super::{color_utils::ColorUtils, config::Config};
+use crate::row_reader::RowReader;
+
+use super::{
+    color_utils::ColorUtils,
+    config::Config,
+    neighbourhood::{Neighbourhood, Side},
+    packed_canvas::{packed_distance, unpack, PackedCanvas},
+    search_control::{CancellationToken, ProgressObserver, SearchOutcome},
+    text_template::TextTemplate,
+};
+
+pub struct PixelArtMatch {
+    pub coordinates: Vec<(u32, u32)>,
+    pub color: Rgb<u8>,
+    /// How clearly the instance stands out, the weakest border contrast reduced by the strongest
+    /// deviation inside the body.
+    pub score: u8,
+}
+
+impl PixelArtMatch {
+    pub fn bounding_box(&self) -> ((u32, u32), (u32, u32)) {
+        let min_x = self.coordinates.iter().map(|&(x, _)| x).min().unwrap_or(0);
+        let min_y = self.coordinates.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        let max_x = self.coordinates.iter().map(|&(x, _)| x).max().unwrap_or(0);
+        let max_y = self.coordinates.iter().map(|&(_, y)| y).max().unwrap_or(0);
+
+        ((min_x, min_y), (max_x, max_y))
+    }
+}
+
+struct Probes {
+    first_index_offset: isize,
+    order: Vec<Probe>,
+    border_bounds: ((i32, i32), (i32, i32)),
+}
+
+struct Probe {
+    x: i32,
+    y: i32,
+    index_offset: isize,
+    kind: ProbeKind,
+}
+
+#[derive(PartialEq)]
+enum ProbeKind {
+    Body,
+    Border,
+    /// First pixel of a color role, has to contrast with the body
+    RoleReference,
+    /// Other pixels of a color role, have to match the first pixel of the role
+    Role {
+        reference_index_offset: isize,
+    },
+}
 
 pub struct PixelArt {
     config: Config,
     coordinates: Vec<(u32, u32)>,
     coordinates_of_adjacent_pixels: Vec<(i32, i32)>,
+    color_roles: Vec<Vec<(u32, u32)>>,
 }
 
 #[derive(Debug)]
@@ -35,8 +93,47 @@ impl PixelArt {
     pub fn new(image: RgbImage, config: Config) -> Result<Self> {
         let coordinates =
             PixelArt::get_coordinates(&image, &config.searched_color, &config.extracting_tolerance);
-        let coordinates_of_adjacent_pixels =
-            PixelArt::get_coordinates_of_adjacent_pixels(&coordinates);
+        let coordinates_of_adjacent_pixels = PixelArt::get_coordinates_of_adjacent_pixels(
+            &coordinates,
+            &config.neighbourhood,
+            &config.optional_sides,
+        );
+
+        if coordinates.is_empty() {
+            return Err(anyhow!(PixelArtError::EmptyCoordinates));
+        }
+
+        Ok(PixelArt {
+            coordinates,
+            coordinates_of_adjacent_pixels,
+            color_roles: vec![],
+            config,
+        })
+    }
+
+    /// Builds the pixel art from a template where opaque pixels form the body and pixels of the
+    /// border marker color have to contrast with it. Transparent pixels are not checked at all.
+    pub fn from_rgba(image: RgbaImage, config: Config) -> Result<Self> {
+        let mut coordinates = vec![];
+        let mut coordinates_of_adjacent_pixels = vec![];
+
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let [r, g, b, a] = pixel.0;
+
+            if a < OPAQUE_ALPHA_THRESHOLD {
+                continue;
+            }
+
+            if ColorUtils::equal_with_tolerance(
+                &Rgb([r, g, b]),
+                &config.border_marker_color,
+                config.extracting_tolerance,
+            ) {
+                coordinates_of_adjacent_pixels.push((x as i32, y as i32));
+            } else {
+                coordinates.push((x, y));
+            }
+        }
 
         if coordinates.is_empty() {
             return Err(anyhow!(PixelArtError::EmptyCoordinates));
@@ -45,6 +142,28 @@ impl PixelArt {
         Ok(PixelArt {
             coordinates,
             coordinates_of_adjacent_pixels,
+            color_roles: vec![],
+            config,
+        })
+    }
+
+    pub fn from_text(text: &str, config: Config) -> Result<Self> {
+        PixelArt::from_text_template(TextTemplate::parse(text)?, config)
+    }
+
+    pub fn from_text_file(path: &PathBuf, config: Config) -> Result<Self> {
+        PixelArt::from_text_template(TextTemplate::load(path)?, config)
+    }
+
+    fn from_text_template(template: TextTemplate, config: Config) -> Result<Self> {
+        if template.coordinates.is_empty() {
+            return Err(anyhow!(PixelArtError::EmptyCoordinates));
+        }
+
+        Ok(PixelArt {
+            coordinates: template.coordinates,
+            coordinates_of_adjacent_pixels: template.coordinates_of_adjacent_pixels,
+            color_roles: template.color_roles.into_values().collect(),
             config,
         })
     }
@@ -77,11 +196,16 @@ impl PixelArt {
         coordinates
     }
 
-    fn get_coordinates_of_adjacent_pixels(coordinates: &Vec<(u32, u32)>) -> Vec<(i32, i32)> {
+    pub(super) fn get_coordinates_of_adjacent_pixels(
+        coordinates: &Vec<(u32, u32)>,
+        neighbourhood: &Neighbourhood,
+        optional_sides: &[Side],
+    ) -> Vec<(i32, i32)> {
         let mut adjacent_coordinates = HashSet::new();
+        let offsets = neighbourhood.offsets();
 
         for (x, y) in coordinates {
-            for (offset_x, offset_y) in SURROUNDING_OFFSETS {
+            for &(offset_x, offset_y) in &offsets {
                 let x_with_offset = *x as i32 + offset_x;
                 let y_with_offset = *y as i32 + offset_y;
 
@@ -98,86 +222,330 @@ impl PixelArt {
             }
         }
 
-        adjacent_coordinates.into_iter().collect()
+        let min_x = coordinates
+            .iter()
+            .map(|&(x, _)| x as i32)
+            .min()
+            .unwrap_or(0);
+        let min_y = coordinates
+            .iter()
+            .map(|&(_, y)| y as i32)
+            .min()
+            .unwrap_or(0);
+        let max_x = coordinates
+            .iter()
+            .map(|&(x, _)| x as i32)
+            .max()
+            .unwrap_or(0);
+        let max_y = coordinates
+            .iter()
+            .map(|&(_, y)| y as i32)
+            .max()
+            .unwrap_or(0);
+
+        adjacent_coordinates
+            .into_iter()
+            .filter(|&coord| {
+                !Side::sides_of(coord, (min_x, min_y), (max_x, max_y))
+                    .iter()
+                    .any(|side| optional_sides.contains(side))
+            })
+            .collect()
     }
 
-    pub fn search_in_image(&self, searched_image: &RgbImage) -> Vec<Vec<(u32, u32)>> {
-        let (img_width, img_height) = searched_image.dimensions();
-        let (window_width, window_height) = self.get_window_size();
+    pub fn search_in_image(&self, searched_image: &RgbImage) -> Vec<PixelArtMatch> {
+        let canvas = PackedCanvas::from_image(searched_image);
+        let probes = self.get_probes(canvas.dimensions().0);
 
-        let found_instances: Vec<Vec<(u32, u32)>> = (0..(img_height - window_height))
+        let found_instances: Vec<PixelArtMatch> = (0..self.get_rows_to_search(&canvas))
             .into_par_iter()
-            .flat_map(|offset_y| {
-                (0..(img_width - window_width))
-                    .into_par_iter()
-                    .filter_map(move |offset_x| {
-                        self.pixel_art_instance_in_window(offset_x, offset_y, searched_image)
-                    })
-            })
+            .flat_map_iter(|offset_y| self.search_row(offset_y, &canvas, &probes))
             .collect();
 
         found_instances
     }
 
-    fn pixel_art_instance_in_window(
+    /// Reports matches through `on_match` as soon as their row is scanned instead of collecting
+    /// them, so the order of matches is not preserved. Rows that were not started yet are
+    /// skipped once the token gets cancelled, a search is only reported as cancelled when that
+    /// left rows unscanned.
+    pub fn search_in_image_streaming<F>(
         &self,
-        offset_x: u32,
-        offset_y: u32,
         searched_image: &RgbImage,
-    ) -> Option<Vec<(u32, u32)>> {
-        let coordinates_with_offset: Vec<(u32, u32)> = self
-            .coordinates
-            .iter()
-            .map(|&(x, y)| (x + offset_x, y + offset_y))
-            .collect();
+        on_match: F,
+        progress_observer: &dyn ProgressObserver,
+        cancellation_token: &CancellationToken,
+    ) -> SearchOutcome
+    where
+        F: Fn(PixelArtMatch) + Sync + Send,
+    {
+        let canvas = PackedCanvas::from_image(searched_image);
+        let probes = self.get_probes(canvas.dimensions().0);
+        let rows_total = self.get_rows_to_search(&canvas);
+        let rows_done = AtomicU32::new(0);
+
+        (0..rows_total).into_par_iter().for_each(|offset_y| {
+            if cancellation_token.is_cancelled() {
+                return;
+            }
 
-        let first_pixel_color =
-            searched_image.get_pixel(coordinates_with_offset[0].0, coordinates_with_offset[0].1);
+            self.search_row(offset_y, &canvas, &probes)
+                .for_each(&on_match);
 
-        for &(x, y) in &coordinates_with_offset {
-            let pixel_color = searched_image.get_pixel(x, y);
+            let rows_done = rows_done.fetch_add(1, Ordering::Relaxed) + 1;
+            progress_observer.on_progress(rows_done, rows_total);
+        });
 
-            if !ColorUtils::equal_with_tolerance(
-                first_pixel_color,
-                pixel_color,
-                self.config.searching_similarity_tolerance,
-            ) {
-                return None;
+        if rows_done.into_inner() < rows_total {
+            return SearchOutcome::Cancelled;
+        }
+
+        SearchOutcome::Completed
+    }
+
+    /// Scans an image read row by row in strips of `strip_height` rows of windows. Only the strip
+    /// and the rows above and below it that the template reaches into are kept in memory, which
+    /// bounds the memory use by the image width instead of its size.
+    pub fn search_in_image_tiled(
+        &self,
+        row_reader: &mut dyn RowReader,
+        strip_height: u32,
+    ) -> Result<Vec<PixelArtMatch>> {
+        let (img_width, img_height) = row_reader.dimensions();
+        let mut canvas = PackedCanvas::new_strip(img_width, img_height);
+        let probes = self.get_probes(img_width);
+        let rows_total = self.get_rows_to_search(&canvas);
+
+        let ((_, border_min_y), (_, border_max_y)) = probes.border_bounds;
+        let (_, window_height) = self.get_window_size();
+        let margin_above = (-border_min_y).max(0) as u32;
+        let margin_below = border_max_y.max(window_height as i32 - 1).max(0) as u32;
+
+        let mut rgb_row = vec![0; img_width as usize * 3];
+        let mut found_instances = vec![];
+
+        for strip_start in (0..rows_total).step_by(strip_height.max(1) as usize) {
+            let strip_end = (strip_start + strip_height.max(1)).min(rows_total);
+            let last_needed_row = (strip_end - 1 + margin_below).min(img_height - 1);
+
+            canvas.drop_rows_before(strip_start.saturating_sub(margin_above));
+
+            while canvas.rows().1 <= last_needed_row {
+                row_reader.read_row(&mut rgb_row)?;
+                canvas.push_row(&rgb_row);
             }
+
+            let strip_instances: Vec<PixelArtMatch> = (strip_start..strip_end)
+                .into_par_iter()
+                .flat_map_iter(|offset_y| self.search_row(offset_y, &canvas, &probes))
+                .collect();
+
+            found_instances.extend(strip_instances);
         }
 
-        let coordinates_of_adjacent_pixels_with_offset: Vec<(i32, i32)> = self
+        Ok(found_instances)
+    }
+
+    fn get_rows_to_search(&self, canvas: &PackedCanvas) -> u32 {
+        let (_, img_height) = canvas.dimensions();
+        let (_, window_height) = self.get_window_size();
+
+        img_height.saturating_sub(window_height)
+    }
+
+    fn search_row<'a>(
+        &'a self,
+        offset_y: u32,
+        canvas: &'a PackedCanvas,
+        probes: &'a Probes,
+    ) -> impl Iterator<Item = PixelArtMatch> + 'a {
+        let (img_width, _) = canvas.dimensions();
+        let (window_width, _) = self.get_window_size();
+
+        (0..img_width.saturating_sub(window_width)).filter_map(move |offset_x| {
+            self.pixel_art_instance_in_window(offset_x, offset_y, canvas, probes)
+        })
+    }
+
+    /// Orders the pixels to compare so that most windows get rejected after a couple of reads.
+    /// The body pixel furthest from the first one is most likely to differ from it and the border
+    /// pixel closest to it is most likely to continue the same colored area.
+    fn get_probes(&self, img_width: u32) -> Probes {
+        let (first_x, first_y) = self.coordinates[0];
+        let squared_distance = |x: i32, y: i32| {
+            let dx = x - first_x as i32;
+            let dy = y - first_y as i32;
+
+            dx * dx + dy * dy
+        };
+        let flat_offset = |x: i32, y: i32| y as isize * img_width as isize + x as isize;
+
+        let mut body: Vec<Probe> = self
+            .coordinates
+            .iter()
+            .skip(1)
+            .map(|&(x, y)| Probe {
+                x: x as i32,
+                y: y as i32,
+                index_offset: flat_offset(x as i32, y as i32),
+                kind: ProbeKind::Body,
+            })
+            .collect();
+        let mut border: Vec<Probe> = self
             .coordinates_of_adjacent_pixels
             .iter()
-            .map(|&(x, y)| (x + offset_x as i32, y + offset_y as i32))
+            .map(|&(x, y)| Probe {
+                x,
+                y,
+                index_offset: flat_offset(x, y),
+                kind: ProbeKind::Border,
+            })
             .collect();
 
-        for (x, y) in coordinates_of_adjacent_pixels_with_offset {
-            if x < 0 || y < 0 {
-                continue;
+        let mut roles: Vec<Probe> = vec![];
+        for role in &self.color_roles {
+            let (reference_x, reference_y) = role[0];
+            let reference_index_offset = flat_offset(reference_x as i32, reference_y as i32);
+
+            for (index, &(x, y)) in role.iter().enumerate() {
+                roles.push(Probe {
+                    x: x as i32,
+                    y: y as i32,
+                    index_offset: flat_offset(x as i32, y as i32),
+                    kind: match index {
+                        0 => ProbeKind::RoleReference,
+                        _ => ProbeKind::Role {
+                            reference_index_offset,
+                        },
+                    },
+                });
             }
+        }
 
-            let adjacent_pixel_color = searched_image.get_pixel_checked(x as u32, y as u32);
+        body.sort_by_key(|probe| -squared_distance(probe.x, probe.y));
+        border.sort_by_key(|probe| squared_distance(probe.x, probe.y));
 
-            if let Some(adjacent_pixel_color) = adjacent_pixel_color {
-                if ColorUtils::equal_with_tolerance(
-                    first_pixel_color,
-                    adjacent_pixel_color,
-                    self.config.searching_contrast_tolerance,
-                ) {
-                    return None;
+        let mut order = vec![];
+        let mut body = body.into_iter();
+        let mut border = border.into_iter();
+        order.extend(body.next());
+        order.extend(border.next());
+        order.extend(body);
+        order.extend(roles);
+        order.extend(border);
+
+        let border_min_x = self
+            .coordinates_of_adjacent_pixels
+            .iter()
+            .map(|&(x, _)| x)
+            .min();
+        let border_min_y = self
+            .coordinates_of_adjacent_pixels
+            .iter()
+            .map(|&(_, y)| y)
+            .min();
+        let border_max_x = self
+            .coordinates_of_adjacent_pixels
+            .iter()
+            .map(|&(x, _)| x)
+            .max();
+        let border_max_y = self
+            .coordinates_of_adjacent_pixels
+            .iter()
+            .map(|&(_, y)| y)
+            .max();
+
+        Probes {
+            first_index_offset: flat_offset(first_x as i32, first_y as i32),
+            order,
+            border_bounds: (
+                (border_min_x.unwrap_or(0), border_min_y.unwrap_or(0)),
+                (border_max_x.unwrap_or(0), border_max_y.unwrap_or(0)),
+            ),
+        }
+    }
+
+    fn pixel_art_instance_in_window(
+        &self,
+        offset_x: u32,
+        offset_y: u32,
+        canvas: &PackedCanvas,
+        probes: &Probes,
+    ) -> Option<PixelArtMatch> {
+        let (img_width, img_height) = canvas.dimensions();
+        let ((border_min_x, border_min_y), (border_max_x, border_max_y)) = probes.border_bounds;
+
+        // Windows whose whole border lies within the image can skip bounds checks
+        let inside_image = offset_x as i32 + border_min_x >= 0
+            && offset_y as i32 + border_min_y >= 0
+            && offset_x as i32 + border_max_x < img_width as i32
+            && offset_y as i32 + border_max_y < img_height as i32;
+
+        let window_index = canvas.index_of(offset_x, offset_y) as isize;
+        let first_pixel_color = canvas.get((window_index + probes.first_index_offset) as usize);
+
+        let mut highest_body_distance = 0;
+        let mut lowest_border_distance = u8::MAX;
+
+        for probe in &probes.order {
+            let pixel_color = if inside_image || probe.kind != ProbeKind::Border {
+                canvas.get((window_index + probe.index_offset) as usize)
+            } else {
+                match canvas.get_checked(probe.x + offset_x as i32, probe.y + offset_y as i32) {
+                    Some(pixel_color) => pixel_color,
+                    None => continue,
+                }
+            };
+
+            let reference_color = match probe.kind {
+                ProbeKind::Role {
+                    reference_index_offset,
+                } => canvas.get((window_index + reference_index_offset) as usize),
+                _ => first_pixel_color,
+            };
+
+            let distance = packed_distance(reference_color, pixel_color);
+
+            match probe.kind {
+                ProbeKind::Body | ProbeKind::Role { .. } => {
+                    if distance > self.config.searching_similarity_tolerance {
+                        return None;
+                    }
+
+                    highest_body_distance = highest_body_distance.max(distance);
+                }
+                ProbeKind::Border | ProbeKind::RoleReference => {
+                    if distance <= self.config.searching_contrast_tolerance {
+                        return None;
+                    }
+
+                    lowest_border_distance = lowest_border_distance.min(distance);
                 }
             }
         }
 
-        Some(coordinates_with_offset)
+        let coordinates_with_offset: Vec<(u32, u32)> = self
+            .coordinates
+            .iter()
+            .map(|&(x, y)| (x + offset_x, y + offset_y))
+            .collect();
+
+        Some(PixelArtMatch {
+            coordinates: coordinates_with_offset,
+            color: unpack(first_pixel_color),
+            score: lowest_border_distance.saturating_sub(highest_body_distance),
+        })
     }
 
     fn get_window_size(&self) -> (u32, u32) {
         let mut highest_x = 0;
         let mut highest_y = 0;
 
-        for &(x, y) in &self.coordinates {
+        for &(x, y) in self
+            .coordinates
+            .iter()
+            .chain(self.color_roles.iter().flatten())
+        {
             if x > highest_x {
                 highest_x = x;
             }
@@ -190,7 +558,7 @@ impl PixelArt {
 
     pub fn visualize_pixel_arts(
         original_image: &RgbImage,
-        pixel_art_instances: &Vec<Vec<(u32, u32)>>,
+        pixel_art_instances: &[PixelArtMatch],
         pixel_art_color: &Rgb<u8>,
         background_color: &Rgb<u8>,
     ) -> RgbImage {
@@ -199,7 +567,7 @@ impl PixelArt {
         let mut visualization = ImageBuffer::from_pixel(img_width, img_height, *background_color);
 
         for instance in pixel_art_instances {
-            for &(x, y) in instance {
+            for &(x, y) in &instance.coordinates {
                 visualization.put_pixel(x, y, *pixel_art_color)
             }
         }
@@ -208,34 +576,30 @@ impl PixelArt {
     }
 }
 
-const SURROUNDING_OFFSETS: [(i32, i32); 8] = [
-    (LEFT, TOP),
-    (CENTER, TOP),
-    (RIGHT, TOP),
-    (LEFT, CENTER),
-    (RIGHT, CENTER),
-    (LEFT, BOTTOM),
-    (CENTER, BOTTOM),
-    (RIGHT, BOTTOM),
-];
-
-const LEFT: i32 = -1;
-const RIGHT: i32 = 1;
-const TOP: i32 = -1;
-const BOTTOM: i32 = 1;
-const CENTER: i32 = 0;
+const OPAQUE_ALPHA_THRESHOLD: u8 = 128;
 
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
 
-    use crate::{image_io::ImageIO, pixel_art_scanner::Config};
+    use image::{Rgb, RgbImage};
+
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc,
+    };
+
+    use crate::{
+        image_io::ImageIO,
+        pixel_art_scanner::{CancellationToken, Config, Neighbourhood, SearchOutcome, Side},
+        row_reader::{PngRowReader, RawRgbRowReader},
+    };
 
     use super::PixelArt;
 
     #[test]
     fn test_search_in_image() {
-        let images = ImageIO::load_multiple_rgb_images(&vec![
+        let images = ImageIO::load_multiple_rgb_images(&[
             PathBuf::from("assets/images/4_crewmates_adjacent_test.png"),
             PathBuf::from("assets/images/4_crewmates_adjacent_test_2.png"),
             PathBuf::from("assets/images/8_crewmates.png"),
@@ -255,4 +619,207 @@ mod tests {
             assert_eq!(found_instances, expected_instances);
         }
     }
+
+    #[test]
+    fn test_search_in_image_with_rgba_template() {
+        let images = ImageIO::load_multiple_rgb_images(&[
+            PathBuf::from("assets/images/4_crewmates_adjacent_test.png"),
+            PathBuf::from("assets/images/4_crewmates_adjacent_test_2.png"),
+            PathBuf::from("assets/images/8_crewmates.png"),
+            PathBuf::from("assets/images/crewmate_with_borders.png"),
+        ])
+        .unwrap();
+        let expected = [4, 4, 8, 1];
+
+        let target_image =
+            ImageIO::load_rgba_image(&PathBuf::from("assets/images/crewmate_rgba.png")).unwrap();
+        let target_pixel_art = PixelArt::from_rgba(target_image, Config::new_default()).unwrap();
+
+        for (index, image) in images.iter().enumerate() {
+            let found_instances = target_pixel_art.search_in_image(image).len();
+            let expected_instances = expected[index];
+
+            assert_eq!(found_instances, expected_instances);
+        }
+    }
+
+    #[test]
+    fn test_search_with_neighbourhood() {
+        let black = Rgb([0, 0, 0]);
+
+        let mut template = RgbImage::from_pixel(4, 4, Rgb([255, 255, 255]));
+        for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)] {
+            template.put_pixel(x, y, black);
+        }
+
+        // A 2x2 square touched diagonally at the bottom right and directly from the top
+        let mut searched_image = RgbImage::from_pixel(10, 10, Rgb([255, 255, 255]));
+        for (x, y) in [(4, 4), (5, 4), (4, 5), (5, 5), (6, 6), (4, 3)] {
+            searched_image.put_pixel(x, y, black);
+        }
+
+        let search = |neighbourhood: Neighbourhood, optional_sides: Vec<Side>| {
+            let mut config = Config::new_default();
+            config.neighbourhood = neighbourhood;
+            config.optional_sides = optional_sides;
+
+            let pixel_art = PixelArt::new(template.clone(), config).unwrap();
+
+            pixel_art.search_in_image(&searched_image).len()
+        };
+
+        assert_eq!(search(Neighbourhood::Eight, vec![]), 0);
+        assert_eq!(search(Neighbourhood::Eight, vec![Side::Top]), 0);
+        assert_eq!(search(Neighbourhood::Four, vec![]), 0);
+        assert_eq!(search(Neighbourhood::Four, vec![Side::Top]), 1);
+        assert_eq!(search(Neighbourhood::Ring(2), vec![Side::Top]), 0);
+        assert_eq!(
+            search(Neighbourhood::Ring(2), vec![Side::Top, Side::Bottom]),
+            1
+        );
+    }
+
+    #[test]
+    fn test_search_in_image_streaming() {
+        let image =
+            ImageIO::load_rgb_image(&PathBuf::from("assets/images/8_crewmates.png")).unwrap();
+        let target_image =
+            ImageIO::load_rgb_image(&PathBuf::from("assets/images/crewmate.png")).unwrap();
+        let target_pixel_art = PixelArt::new(target_image, Config::new_default()).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let last_progress = AtomicU32::new(0);
+
+        let outcome = target_pixel_art.search_in_image_streaming(
+            &image,
+            |found| sender.send(found).unwrap(),
+            &|rows_done: u32, rows_total: u32| {
+                assert!(rows_done <= rows_total);
+                last_progress.fetch_max(rows_done, Ordering::Relaxed);
+            },
+            &CancellationToken::new(),
+        );
+        drop(sender);
+
+        assert_eq!(outcome, SearchOutcome::Completed);
+        assert_eq!(receiver.iter().count(), 8);
+        assert_eq!(last_progress.load(Ordering::Relaxed), 11 - 4);
+
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+
+        let outcome = target_pixel_art.search_in_image_streaming(
+            &image,
+            |_| panic!("Cancelled search should not report matches"),
+            &|_, _| {},
+            &cancellation_token,
+        );
+
+        assert_eq!(outcome, SearchOutcome::Cancelled);
+
+        // Cancelling once every row is scanned doesn't change the outcome
+        let cancellation_token = CancellationToken::new();
+
+        let outcome = target_pixel_art.search_in_image_streaming(
+            &image,
+            |_| {},
+            &|rows_done: u32, rows_total: u32| {
+                if rows_done == rows_total {
+                    cancellation_token.cancel();
+                }
+            },
+            &cancellation_token,
+        );
+
+        assert_eq!(outcome, SearchOutcome::Completed);
+    }
+
+    #[test]
+    fn test_search_in_image_tiled() {
+        let path = PathBuf::from("assets/images/8_crewmates.png");
+        let image = ImageIO::load_rgb_image(&path).unwrap();
+        let target_image =
+            ImageIO::load_rgb_image(&PathBuf::from("assets/images/crewmate.png")).unwrap();
+        let target_pixel_art = PixelArt::new(target_image, Config::new_default()).unwrap();
+
+        let expected: Vec<Vec<(u32, u32)>> = target_pixel_art
+            .search_in_image(&image)
+            .into_iter()
+            .map(|found| found.coordinates)
+            .collect();
+
+        // Removes the raw copy even when an assertion below fails.
+        struct RemoveOnDrop(PathBuf);
+
+        impl Drop for RemoveOnDrop {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+
+        let raw_path =
+            std::env::temp_dir().join(format!("pixel_crab_8_crewmates_{}.rgb", std::process::id()));
+        let _raw_file = RemoveOnDrop(raw_path.clone());
+        std::fs::write(&raw_path, image.as_raw()).unwrap();
+
+        for strip_height in [1, 2, 5, 100] {
+            let mut png_reader = PngRowReader::open(&path).unwrap();
+            let mut raw_reader =
+                RawRgbRowReader::open(&raw_path, image.width(), image.height()).unwrap();
+
+            for found_instances in [
+                target_pixel_art.search_in_image_tiled(&mut png_reader, strip_height),
+                target_pixel_art.search_in_image_tiled(&mut raw_reader, strip_height),
+            ] {
+                let found_instances: Vec<Vec<(u32, u32)>> = found_instances
+                    .unwrap()
+                    .into_iter()
+                    .map(|found| found.coordinates)
+                    .collect();
+
+                assert_eq!(found_instances, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_in_image_with_text_template() {
+        let images = ImageIO::load_multiple_rgb_images(&[
+            PathBuf::from("assets/images/4_crewmates_adjacent_test.png"),
+            PathBuf::from("assets/images/4_crewmates_adjacent_test_2.png"),
+            PathBuf::from("assets/images/8_crewmates.png"),
+            PathBuf::from("assets/images/crewmate_with_borders.png"),
+        ])
+        .unwrap();
+        let templates = [
+            ("assets/templates/crewmate.txt", [4, 4, 8, 1]),
+            ("assets/templates/crewmate_with_visor.txt", [4, 4, 8, 1]),
+        ];
+
+        for (template_path, expected) in templates {
+            let target_pixel_art =
+                PixelArt::from_text_file(&PathBuf::from(template_path), Config::new_default())
+                    .unwrap();
+
+            for (index, image) in images.iter().enumerate() {
+                let found_instances = target_pixel_art.search_in_image(image).len();
+                let expected_instances = expected[index];
+
+                assert_eq!(found_instances, expected_instances);
+            }
+        }
+
+        let mut searched_image = RgbImage::from_pixel(5, 2, Rgb([255, 255, 255]));
+        searched_image.put_pixel(0, 0, Rgb([0, 0, 0]));
+        searched_image.put_pixel(1, 0, Rgb([255, 0, 0]));
+        searched_image.put_pixel(2, 0, Rgb([255, 0, 0]));
+
+        let target_pixel_art = PixelArt::from_text("#aa", Config::new_default()).unwrap();
+
+        assert_eq!(target_pixel_art.search_in_image(&searched_image).len(), 1);
+
+        searched_image.put_pixel(2, 0, Rgb([0, 255, 0]));
+
+        assert_eq!(target_pixel_art.search_in_image(&searched_image).len(), 0);
+    }
 }
@@ -0,0 +1,44 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Shared flag for stopping a running search from another thread. Clones refer to the same flag.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+pub trait ProgressObserver: Sync {
+    /// Called from the worker threads every time a row of windows is finished.
+    fn on_progress(&self, rows_done: u32, rows_total: u32);
+}
+
+impl<F> ProgressObserver for F
+where
+    F: Fn(u32, u32) + Sync,
+{
+    fn on_progress(&self, rows_done: u32, rows_total: u32) {
+        self(rows_done, rows_total)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SearchOutcome {
+    Completed,
+    Cancelled,
+}
@@ -0,0 +1,145 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{config::SuppressionConfig, pixel_art::PixelArtMatch};
+
+pub struct SuppressionResult {
+    pub kept: Vec<PixelArtMatch>,
+    pub suppressed: usize,
+}
+
+pub struct OverlapSuppressor {
+    config: SuppressionConfig,
+}
+
+impl OverlapSuppressor {
+    pub fn new(config: SuppressionConfig) -> OverlapSuppressor {
+        OverlapSuppressor { config }
+    }
+
+    /// Non-maximum suppression, matches are accepted from the highest score down and every match
+    /// overlapping an already accepted one is dropped. Kept matches stay in their original order.
+    pub fn suppress(&self, matches: Vec<PixelArtMatch>) -> SuppressionResult {
+        let total = matches.len();
+
+        let mut order: Vec<usize> = (0..total).collect();
+        order.sort_by(|&a, &b| matches[b].score.cmp(&matches[a].score));
+
+        let mut kept_indexes = vec![];
+        let mut taken_pixels: HashSet<(u32, u32)> = HashSet::new();
+        let mut boxes_by_pixel: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+
+        for index in order {
+            let candidate = &matches[index];
+
+            if !self.config.allow_shared_pixels
+                && candidate
+                    .coordinates
+                    .iter()
+                    .any(|pixel| taken_pixels.contains(pixel))
+            {
+                continue;
+            }
+
+            let ((min_x, min_y), (max_x, max_y)) = candidate.bounding_box();
+
+            let mut overlapping: HashSet<usize> = HashSet::new();
+            for y in min_y..=max_y {
+                for x in min_x..=max_x {
+                    if let Some(kept) = boxes_by_pixel.get(&(x, y)) {
+                        overlapping.extend(kept);
+                    }
+                }
+            }
+
+            if overlapping.iter().any(|&kept| {
+                intersection_over_union(candidate, &matches[kept]) > self.config.max_overlap
+            }) {
+                continue;
+            }
+
+            taken_pixels.extend(&candidate.coordinates);
+            for y in min_y..=max_y {
+                for x in min_x..=max_x {
+                    boxes_by_pixel.entry((x, y)).or_default().push(index);
+                }
+            }
+
+            kept_indexes.push(index);
+        }
+
+        kept_indexes.sort();
+
+        let suppressed = total - kept_indexes.len();
+        let mut matches: Vec<Option<PixelArtMatch>> = matches.into_iter().map(Some).collect();
+        let kept = kept_indexes
+            .into_iter()
+            .filter_map(|index| matches[index].take())
+            .collect();
+
+        SuppressionResult { kept, suppressed }
+    }
+}
+
+fn intersection_over_union(a: &PixelArtMatch, b: &PixelArtMatch) -> f32 {
+    let ((a_min_x, a_min_y), (a_max_x, a_max_y)) = a.bounding_box();
+    let ((b_min_x, b_min_y), (b_max_x, b_max_y)) = b.bounding_box();
+
+    let left = a_min_x.max(b_min_x);
+    let top = a_min_y.max(b_min_y);
+    let right = a_max_x.min(b_max_x);
+    let bottom = a_max_y.min(b_max_y);
+
+    if left > right || top > bottom {
+        return 0.0;
+    }
+
+    let area = |min_x: u32, min_y: u32, max_x: u32, max_y: u32| {
+        ((max_x - min_x + 1) * (max_y - min_y + 1)) as f32
+    };
+
+    let intersection = area(left, top, right, bottom);
+    let union = area(a_min_x, a_min_y, a_max_x, a_max_y) + area(b_min_x, b_min_y, b_max_x, b_max_y)
+        - intersection;
+
+    intersection / union
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use crate::pixel_art_scanner::{PixelArtMatch, SuppressionConfig};
+
+    use super::OverlapSuppressor;
+
+    fn square(left: u32, top: u32, score: u8) -> PixelArtMatch {
+        PixelArtMatch {
+            coordinates: vec![
+                (left, top),
+                (left + 1, top),
+                (left, top + 1),
+                (left + 1, top + 1),
+            ],
+            color: Rgb([0, 0, 0]),
+            score,
+        }
+    }
+
+    #[test]
+    fn test_suppress_overlapping_matches() {
+        let matches = || vec![square(0, 0, 10), square(1, 0, 20), square(5, 5, 1)];
+
+        let strict = OverlapSuppressor::new(SuppressionConfig::new(false, 0.5));
+        let result = strict.suppress(matches());
+
+        assert_eq!(result.suppressed, 1);
+        assert_eq!(result.kept[0].coordinates[0], (1, 0));
+        assert_eq!(result.kept[1].coordinates[0], (5, 5));
+
+        let sharing = OverlapSuppressor::new(SuppressionConfig::new(true, 0.5));
+        let result = sharing.suppress(matches());
+
+        assert_eq!(result.suppressed, 0);
+        assert_eq!(result.kept.len(), 3);
+    }
+}
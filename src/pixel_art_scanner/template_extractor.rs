@@ -0,0 +1,229 @@
+use core::fmt;
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use image::{Rgb, RgbImage};
+
+use super::{
+    color_utils::ColorUtils, config::Config, pixel_art::PixelArt, text_template::TextTemplate,
+};
+
+/// Area of the canvas given by its top left corner, width and height.
+#[derive(Debug, Clone, Copy)]
+pub struct Area {
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug)]
+pub enum TemplateExtractorError {
+    AreaOutsideImage,
+    SeedOutsideArea,
+}
+
+impl fmt::Display for TemplateExtractorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateExtractorError::AreaOutsideImage => {
+                write!(f, "Area reaches outside of the image")
+            }
+            TemplateExtractorError::SeedOutsideArea => {
+                write!(f, "Seed pixel is not inside of the area")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateExtractorError {}
+
+/// Shape of a uniform region cut out of a canvas, moved so that it starts at the origin.
+pub struct ExtractedTemplate {
+    pub shape: Vec<(u32, u32)>,
+}
+
+impl ExtractedTemplate {
+    /// Paints the shape with the searched color of the config, which is how `PixelArt::new`
+    /// tells the template pixels apart from the background.
+    pub fn to_image(&self, config: &Config, background_color: &Rgb<u8>) -> RgbImage {
+        let width = self.shape.iter().map(|&(x, _)| x + 1).max().unwrap_or(0);
+        let height = self.shape.iter().map(|&(_, y)| y + 1).max().unwrap_or(0);
+
+        let mut template = RgbImage::from_pixel(width, height, *background_color);
+
+        for &(x, y) in &self.shape {
+            template.put_pixel(x, y, config.searched_color);
+        }
+
+        template
+    }
+
+    /// Writes the shape together with the border the config would derive for it, shifted so that
+    /// the whole border fits into the grid.
+    pub fn to_text_template(&self, config: &Config) -> TextTemplate {
+        let border = PixelArt::get_coordinates_of_adjacent_pixels(
+            &self.shape,
+            &config.neighbourhood,
+            &config.optional_sides,
+        );
+
+        let margin_x = -border.iter().map(|&(x, _)| x).min().unwrap_or(0).min(0);
+        let margin_y = -border.iter().map(|&(_, y)| y).min().unwrap_or(0).min(0);
+
+        let mut coordinates_of_adjacent_pixels: Vec<(i32, i32)> = border
+            .into_iter()
+            .map(|(x, y)| (x + margin_x, y + margin_y))
+            .collect();
+        coordinates_of_adjacent_pixels.sort_by_key(|&(x, y)| (y, x));
+
+        TextTemplate {
+            coordinates: self
+                .shape
+                .iter()
+                .map(|&(x, y)| (x + margin_x as u32, y + margin_y as u32))
+                .collect(),
+            coordinates_of_adjacent_pixels,
+            color_roles: BTreeMap::new(),
+        }
+    }
+}
+
+pub struct TemplateExtractor {
+    config: Config,
+}
+
+impl TemplateExtractor {
+    pub fn new(config: Config) -> TemplateExtractor {
+        TemplateExtractor { config }
+    }
+
+    /// Flood fills the region of the seed color around the seed pixel, without leaving the area.
+    pub fn extract(
+        &self,
+        image: &RgbImage,
+        area: Area,
+        (seed_x, seed_y): (u32, u32),
+    ) -> Result<ExtractedTemplate> {
+        let (img_width, img_height) = image.dimensions();
+        let Area {
+            left,
+            top,
+            width,
+            height,
+        } = area;
+
+        let fits = |start: u32, length: u32, limit: u32| {
+            start.checked_add(length).is_some_and(|end| end <= limit)
+        };
+
+        if !fits(left, width, img_width) || !fits(top, height, img_height) {
+            return Err(anyhow!(TemplateExtractorError::AreaOutsideImage));
+        }
+        if seed_x < left || seed_y < top || seed_x >= left + width || seed_y >= top + height {
+            return Err(anyhow!(TemplateExtractorError::SeedOutsideArea));
+        }
+
+        let seed_color = image.get_pixel(seed_x, seed_y);
+        let connectivity_offsets = self.config.neighbourhood.connectivity_offsets();
+
+        let mut visited = vec![false; (width * height) as usize];
+        let mut region = vec![];
+        let mut stack = vec![(seed_x, seed_y)];
+        visited[((seed_y - top) * width + seed_x - left) as usize] = true;
+
+        while let Some((x, y)) = stack.pop() {
+            region.push((x, y));
+
+            for &(offset_x, offset_y) in &connectivity_offsets {
+                let x_with_offset = x as i32 + offset_x;
+                let y_with_offset = y as i32 + offset_y;
+
+                if x_with_offset < left as i32
+                    || y_with_offset < top as i32
+                    || x_with_offset >= (left + width) as i32
+                    || y_with_offset >= (top + height) as i32
+                {
+                    continue;
+                }
+
+                let (neighbour_x, neighbour_y) = (x_with_offset as u32, y_with_offset as u32);
+                let index = ((neighbour_y - top) * width + neighbour_x - left) as usize;
+
+                if visited[index] {
+                    continue;
+                }
+
+                if ColorUtils::equal_with_tolerance(
+                    seed_color,
+                    image.get_pixel(neighbour_x, neighbour_y),
+                    self.config.extracting_tolerance,
+                ) {
+                    visited[index] = true;
+                    stack.push((neighbour_x, neighbour_y));
+                }
+            }
+        }
+
+        let min_x = region.iter().map(|&(x, _)| x).min().unwrap_or(0);
+        let min_y = region.iter().map(|&(_, y)| y).min().unwrap_or(0);
+
+        let mut shape: Vec<(u32, u32)> = region
+            .into_iter()
+            .map(|(x, y)| (x - min_x, y - min_y))
+            .collect();
+        shape.sort_by_key(|&(x, y)| (y, x));
+
+        Ok(ExtractedTemplate { shape })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::read_to_string, path::PathBuf};
+
+    use image::Rgb;
+
+    use crate::{
+        image_io::ImageIO,
+        pixel_art_scanner::{Config, PixelArt},
+    };
+
+    use super::{Area, TemplateExtractor};
+
+    #[test]
+    fn test_extract_crewmate() {
+        let image =
+            ImageIO::load_rgb_image(&PathBuf::from("assets/images/crewmate_with_borders.png"))
+                .unwrap();
+        let area = Area {
+            left: 0,
+            top: 0,
+            width: 6,
+            height: 6,
+        };
+
+        let extractor = TemplateExtractor::new(Config::new_default());
+        let template = extractor.extract(&image, area, (2, 1)).unwrap();
+
+        let expected_text = read_to_string("assets/templates/crewmate.txt").unwrap();
+        assert_eq!(
+            template
+                .to_text_template(&Config::new_default())
+                .to_string(),
+            expected_text
+        );
+
+        let template_image = template.to_image(&Config::new_default(), &Rgb([255, 255, 255]));
+        let pixel_art = PixelArt::new(template_image, Config::new_default()).unwrap();
+        assert_eq!(pixel_art.search_in_image(&image).len(), 1);
+
+        assert!(extractor.extract(&image, area, (6, 1)).is_err());
+
+        let overflowing_area = Area {
+            left: u32::MAX,
+            ..area
+        };
+        assert!(extractor.extract(&image, overflowing_area, (2, 1)).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use core::fmt;
+use std::{collections::BTreeMap, fs::read_to_string, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+
+/// Template written as a grid of characters: `#` for the body, `.` for pixels that have to
+/// contrast with it, space for pixels that are not checked and letters for additional color
+/// roles. Every role has to be uniform and contrast with the body, different roles are not
+/// compared with each other.
+pub struct TextTemplate {
+    pub coordinates: Vec<(u32, u32)>,
+    pub coordinates_of_adjacent_pixels: Vec<(i32, i32)>,
+    pub color_roles: BTreeMap<char, Vec<(u32, u32)>>,
+}
+
+#[derive(Debug)]
+pub enum TextTemplateError {
+    InvalidCharacter {
+        character: char,
+        line: usize,
+        column: usize,
+    },
+}
+
+impl fmt::Display for TextTemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextTemplateError::InvalidCharacter {
+                character,
+                line,
+                column,
+            } => write!(
+                f,
+                "Invalid character {:?} at line {}, column {}, expected '#', '.', ' ' or a letter",
+                character, line, column
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextTemplateError {}
+
+impl TextTemplate {
+    pub fn parse(text: &str) -> Result<TextTemplate> {
+        let mut coordinates = vec![];
+        let mut coordinates_of_adjacent_pixels = vec![];
+        let mut color_roles: BTreeMap<char, Vec<(u32, u32)>> = BTreeMap::new();
+
+        for (y, line) in text.lines().enumerate() {
+            for (x, character) in line.chars().enumerate() {
+                match character {
+                    BODY => coordinates.push((x as u32, y as u32)),
+                    CONTRAST => coordinates_of_adjacent_pixels.push((x as i32, y as i32)),
+                    IGNORED => {}
+                    character if character.is_ascii_alphabetic() => color_roles
+                        .entry(character)
+                        .or_default()
+                        .push((x as u32, y as u32)),
+                    character => {
+                        return Err(anyhow!(TextTemplateError::InvalidCharacter {
+                            character,
+                            line: y + 1,
+                            column: x + 1,
+                        }))
+                    }
+                }
+            }
+        }
+
+        Ok(TextTemplate {
+            coordinates,
+            coordinates_of_adjacent_pixels,
+            color_roles,
+        })
+    }
+
+    pub fn load(path: &PathBuf) -> Result<TextTemplate> {
+        TextTemplate::parse(&read_to_string(path)?)
+    }
+}
+
+impl fmt::Display for TextTemplate {
+    /// Writes the template back in the text format. Contrast pixels outside of the grid of the
+    /// other pixels can't be represented and are left out.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut cells: BTreeMap<(u32, u32), char> = BTreeMap::new();
+
+        for &(x, y) in &self.coordinates_of_adjacent_pixels {
+            if x >= 0 && y >= 0 {
+                cells.insert((y as u32, x as u32), CONTRAST);
+            }
+        }
+        for (&role, coordinates) in &self.color_roles {
+            for &(x, y) in coordinates {
+                cells.insert((y, x), role);
+            }
+        }
+        for &(x, y) in &self.coordinates {
+            cells.insert((y, x), BODY);
+        }
+
+        let height = cells.keys().map(|&(y, _)| y + 1).max().unwrap_or(0);
+
+        for row in 0..height {
+            let row_cells: Vec<(u32, char)> = cells
+                .range((row, 0)..(row + 1, 0))
+                .map(|(&(_, x), &character)| (x, character))
+                .collect();
+
+            let mut line = String::new();
+            for (x, character) in row_cells {
+                while (line.len() as u32) < x {
+                    line.push(IGNORED);
+                }
+                line.push(character);
+            }
+
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+const BODY: char = '#';
+const CONTRAST: char = '.';
+const IGNORED: char = ' ';
+
+#[cfg(test)]
+mod tests {
+    use super::TextTemplate;
+
+    #[test]
+    fn test_parse_text_template() {
+        let text = " ...\n.#a#.\n.###\n";
+
+        let template = TextTemplate::parse(text).unwrap();
+
+        assert_eq!(
+            template.coordinates,
+            vec![(1, 1), (3, 1), (1, 2), (2, 2), (3, 2)]
+        );
+        assert_eq!(template.color_roles[&'a'], vec![(2, 1)]);
+        assert_eq!(template.coordinates_of_adjacent_pixels.len(), 6);
+        assert_eq!(template.to_string(), text);
+
+        assert!(TextTemplate::parse("#?").is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use core::fmt;
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use memmap2::Mmap;
+use png::{ColorType, Decoder, Reader, Transformations};
+
+/// Source of RGB rows read from top to bottom, so that images can be processed without holding
+/// all of their pixels in memory at once.
+pub trait RowReader {
+    fn dimensions(&self) -> (u32, u32);
+
+    /// Fills `row` with the next `width * 3` bytes of RGB pixels.
+    fn read_row(&mut self, row: &mut [u8]) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub enum RowReaderError {
+    Interlaced,
+    UnsupportedColorType(ColorType),
+    MissingRows,
+    SizeMismatch { expected: u64, actual: u64 },
+}
+
+impl fmt::Display for RowReaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowReaderError::Interlaced => {
+                write!(f, "Interlaced images can't be read row by row")
+            }
+            RowReaderError::UnsupportedColorType(color_type) => {
+                write!(f, "Unsupported color type {:?}", color_type)
+            }
+            RowReaderError::MissingRows => write!(f, "Image ended before its last row"),
+            RowReaderError::SizeMismatch { expected, actual } => write!(
+                f,
+                "Expected {} bytes of raw RGB pixels but the file has {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RowReaderError {}
+
+/// Decodes a PNG row by row, keeping only a single decoded row in memory.
+pub struct PngRowReader {
+    reader: Reader<BufReader<File>>,
+    color_type: ColorType,
+}
+
+impl PngRowReader {
+    pub fn open(path: &PathBuf) -> Result<PngRowReader> {
+        let mut decoder = Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+
+        let reader = decoder.read_info()?;
+
+        if reader.info().interlaced {
+            return Err(anyhow!(RowReaderError::Interlaced));
+        }
+
+        let (color_type, _) = reader.output_color_type();
+
+        Ok(PngRowReader { reader, color_type })
+    }
+}
+
+impl RowReader for PngRowReader {
+    fn dimensions(&self) -> (u32, u32) {
+        let info = self.reader.info();
+
+        (info.width, info.height)
+    }
+
+    fn read_row(&mut self, row: &mut [u8]) -> Result<()> {
+        let color_type = self.color_type;
+        let decoded_row = self
+            .reader
+            .next_row()?
+            .ok_or(anyhow!(RowReaderError::MissingRows))?;
+        let data = decoded_row.data();
+
+        match color_type {
+            ColorType::Rgb => row.copy_from_slice(&data[..row.len()]),
+            ColorType::Rgba => {
+                for (rgb, rgba) in row.chunks_exact_mut(3).zip(data.chunks_exact(4)) {
+                    rgb.copy_from_slice(&rgba[..3]);
+                }
+            }
+            ColorType::Grayscale => {
+                for (rgb, &gray) in row.chunks_exact_mut(3).zip(data) {
+                    rgb.fill(gray);
+                }
+            }
+            ColorType::GrayscaleAlpha => {
+                for (rgb, gray_alpha) in row.chunks_exact_mut(3).zip(data.chunks_exact(2)) {
+                    rgb.fill(gray_alpha[0]);
+                }
+            }
+            color_type => return Err(anyhow!(RowReaderError::UnsupportedColorType(color_type))),
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a headerless file of RGB pixels through a memory map, so only the pages of the rows
+/// being read have to be resident.
+pub struct RawRgbRowReader {
+    mmap: Mmap,
+    width: u32,
+    height: u32,
+    next_row: u32,
+}
+
+impl RawRgbRowReader {
+    pub fn open(path: &PathBuf, width: u32, height: u32) -> Result<RawRgbRowReader> {
+        let file = File::open(path)?;
+
+        // SAFETY: the file is only read and is expected not to be modified while it is mapped
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let expected = width as u64 * height as u64 * 3;
+        let actual = mmap.len() as u64;
+
+        if expected != actual {
+            return Err(anyhow!(RowReaderError::SizeMismatch { expected, actual }));
+        }
+
+        Ok(RawRgbRowReader {
+            mmap,
+            width,
+            height,
+            next_row: 0,
+        })
+    }
+}
+
+impl RowReader for RawRgbRowReader {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn read_row(&mut self, row: &mut [u8]) -> Result<()> {
+        if self.next_row >= self.height {
+            return Err(anyhow!(RowReaderError::MissingRows));
+        }
+
+        let row_length = self.width as usize * 3;
+        let start = self.next_row as usize * row_length;
+
+        row.copy_from_slice(&self.mmap[start..start + row_length]);
+        self.next_row += 1;
+
+        Ok(())
+    }
+}
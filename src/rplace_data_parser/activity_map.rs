@@ -0,0 +1,371 @@
+use std::{
+    fs::{create_dir_all, write},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+
+use crate::{
+    heatmap::{Gradient, Heatmap, HeatmapConfig, HeatmapScale},
+    image_io::ImageIO,
+};
+
+use super::{
+    parser_image::ParserImage,
+    record::Record,
+    replay_observer::ReplayObserver,
+    user_interner::{UserId, UserInterner},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityKind {
+    Placements,
+    DistinctUsers,
+    /// Seconds between the last change of color and the end of the window. Pixels that didn't
+    /// change within the window get the length of the whole window.
+    SecondsSinceLastChange,
+}
+
+impl ActivityKind {
+    pub const ALL: [ActivityKind; 3] = [
+        ActivityKind::Placements,
+        ActivityKind::DistinctUsers,
+        ActivityKind::SecondsSinceLastChange,
+    ];
+
+    fn file_name(&self) -> &'static str {
+        match self {
+            ActivityKind::Placements => "placements",
+            ActivityKind::DistinctUsers => "distinct_users",
+            ActivityKind::SecondsSinceLastChange => "seconds_since_last_change",
+        }
+    }
+}
+
+/// Time window of the records that are counted, both ends inclusive. Without a start or an end
+/// the window reaches to the first or the last record.
+pub struct ActivityMapConfig {
+    pub start: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+}
+
+impl ActivityMapConfig {
+    pub fn new(start: Option<NaiveDateTime>, end: Option<NaiveDateTime>) -> ActivityMapConfig {
+        ActivityMapConfig { start, end }
+    }
+
+    pub fn new_default() -> ActivityMapConfig {
+        ActivityMapConfig {
+            start: None,
+            end: None,
+        }
+    }
+}
+
+/// Per pixel accumulators of the records within a time window, to be used as a
+/// [`ReplayObserver`]. The map grows with the records like the canvas does.
+pub struct ActivityMap {
+    config: ActivityMapConfig,
+    origin: (i32, i32),
+    width: u32,
+    height: u32,
+    placements: Vec<u32>,
+    users: Vec<Vec<UserId>>,
+    last_change: Vec<Option<NaiveDateTime>>,
+    first_timestamp: Option<NaiveDateTime>,
+    last_timestamp: Option<NaiveDateTime>,
+}
+
+impl ActivityMap {
+    pub fn new(config: ActivityMapConfig) -> ActivityMap {
+        ActivityMap {
+            config,
+            origin: (0, 0),
+            width: 0,
+            height: 0,
+            placements: vec![],
+            users: vec![],
+            last_change: vec![],
+            first_timestamp: None,
+            last_timestamp: None,
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Coordinates of the dataset the top left value of the map belongs to.
+    pub fn origin(&self) -> (i32, i32) {
+        self.origin
+    }
+
+    /// Value at coordinates of the dataset, zero outside of the map.
+    pub fn get(&self, kind: ActivityKind, (x, y): (i32, i32)) -> u32 {
+        match self.index_of((x, y)) {
+            Some(index) => self.value_at(kind, index),
+            None => 0,
+        }
+    }
+
+    /// Values laid out row after row.
+    pub fn values(&self, kind: ActivityKind) -> Vec<u32> {
+        (0..self.placements.len())
+            .map(|index| self.value_at(kind, index))
+            .collect()
+    }
+
+    pub fn to_heatmap(&self, kind: ActivityKind, config: HeatmapConfig) -> Heatmap {
+        Heatmap::from_values(self.width, self.height, self.values(kind), config)
+    }
+
+    /// Writes the values as little endian u32 after a header of the width and height as u32
+    /// and the origin as i32, also little endian.
+    pub fn save_raw(&self, kind: ActivityKind, path: &PathBuf) -> Result<()> {
+        let values = self.values(kind);
+        let mut bytes = Vec::with_capacity(RAW_HEADER_LENGTH + values.len() * 4);
+
+        bytes.extend_from_slice(&self.width.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&self.origin.0.to_le_bytes());
+        bytes.extend_from_slice(&self.origin.1.to_le_bytes());
+
+        for value in values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        write(path, bytes)?;
+
+        Ok(())
+    }
+
+    /// Saves a heatmap PNG and a raw array of every kind of activity.
+    pub fn save(&self, output_dir: &str) -> Result<()> {
+        let directory_path = PathBuf::from(output_dir);
+
+        if !directory_path.exists() {
+            create_dir_all(&directory_path)?;
+        }
+
+        for kind in ActivityKind::ALL {
+            let heatmap_config =
+                HeatmapConfig::new(1, Gradient::new_default(), HeatmapScale::Logarithmic, true);
+
+            ImageIO::save_image(
+                &self.to_heatmap(kind, heatmap_config).render(),
+                output_dir,
+                kind.file_name(),
+                ".png",
+            )?;
+            self.save_raw(
+                kind,
+                &directory_path.join(format!("{}.bin", kind.file_name())),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn value_at(&self, kind: ActivityKind, index: usize) -> u32 {
+        match kind {
+            ActivityKind::Placements => self.placements[index],
+            ActivityKind::DistinctUsers => self.users[index].len() as u32,
+            ActivityKind::SecondsSinceLastChange => {
+                let (Some(first_timestamp), Some(last_timestamp)) =
+                    (self.first_timestamp, self.last_timestamp)
+                else {
+                    return 0;
+                };
+
+                let start = self.config.start.unwrap_or(first_timestamp);
+                let end = self.config.end.unwrap_or(last_timestamp);
+                let last_change = self.last_change[index].unwrap_or(start);
+
+                (end - last_change).num_seconds().max(0) as u32
+            }
+        }
+    }
+
+    fn index_of(&self, (x, y): (i32, i32)) -> Option<usize> {
+        let (x, y) = (x - self.origin.0, y - self.origin.1);
+
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+
+        Some(y as usize * self.width as usize + x as usize)
+    }
+
+    fn in_window(&self, timestamp: NaiveDateTime) -> bool {
+        self.config.start.is_none_or(|start| timestamp >= start)
+            && self.config.end.is_none_or(|end| timestamp <= end)
+    }
+
+    /// Grows the map so that it covers the area between the corners, keeping the values.
+    fn expand_to(&mut self, (min_x, min_y): (i32, i32), (max_x, max_y): (i32, i32)) {
+        let (origin_x, origin_y) = self.origin;
+        let (old_max_x, old_max_y) = (
+            origin_x + self.width as i32 - 1,
+            origin_y + self.height as i32 - 1,
+        );
+
+        let (new_min_x, new_min_y, new_max_x, new_max_y) = if self.placements.is_empty() {
+            (min_x, min_y, max_x, max_y)
+        } else {
+            (
+                min_x.min(origin_x),
+                min_y.min(origin_y),
+                max_x.max(old_max_x),
+                max_y.max(old_max_y),
+            )
+        };
+
+        if !self.placements.is_empty()
+            && (new_min_x, new_min_y, new_max_x, new_max_y)
+                == (origin_x, origin_y, old_max_x, old_max_y)
+        {
+            return;
+        }
+
+        let new_width = (new_max_x - new_min_x + 1) as u32;
+        let new_height = (new_max_y - new_min_y + 1) as u32;
+        let length = new_width as usize * new_height as usize;
+
+        let mut placements = vec![0; length];
+        let mut users = vec![vec![]; length];
+        let mut last_change = vec![None; length];
+
+        let (shift_x, shift_y) = (
+            (origin_x - new_min_x) as usize,
+            (origin_y - new_min_y) as usize,
+        );
+
+        for y in 0..self.height as usize {
+            for x in 0..self.width as usize {
+                let old_index = y * self.width as usize + x;
+                let new_index = (y + shift_y) * new_width as usize + x + shift_x;
+
+                placements[new_index] = self.placements[old_index];
+                users[new_index] = std::mem::take(&mut self.users[old_index]);
+                last_change[new_index] = self.last_change[old_index];
+            }
+        }
+
+        self.origin = (new_min_x, new_min_y);
+        self.width = new_width;
+        self.height = new_height;
+        self.placements = placements;
+        self.users = users;
+        self.last_change = last_change;
+    }
+}
+
+impl ReplayObserver for ActivityMap {
+    fn before_record(&mut self, record: &Record, canvas: &ParserImage, _users: &UserInterner) {
+        if !self.in_window(record.timestamp) {
+            return;
+        }
+
+        self.first_timestamp.get_or_insert(record.timestamp);
+        self.last_timestamp = Some(record.timestamp);
+
+        let ((min_x, min_y), (max_x, max_y)) = record.coordinate.bounds();
+        self.expand_to((min_x, min_y), (max_x, max_y));
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if !record.coordinate.contains((x, y)) {
+                    continue;
+                }
+
+                let Some(index) = self.index_of((x, y)) else {
+                    continue;
+                };
+
+                self.placements[index] += 1;
+
+                if let Err(position) = self.users[index].binary_search(&record.user) {
+                    self.users[index].insert(position, record.user);
+                }
+
+                if canvas.get_pixel((x, y)) != record.pixel_color {
+                    self.last_change[index] = Some(record.timestamp);
+                }
+            }
+        }
+    }
+}
+
+const RAW_HEADER_LENGTH: usize = 16;
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::remove_dir_all, path::PathBuf};
+
+    use chrono::NaiveDateTime;
+
+    use crate::rplace_data_parser::{Parser, ParserConfig};
+
+    use super::{ActivityKind, ActivityMap, ActivityMapConfig};
+
+    fn replay(config: ActivityMapConfig) -> ActivityMap {
+        let output_dir = std::env::temp_dir().join(format!(
+            "pixel_crab_test_activity_map_{}",
+            std::process::id()
+        ));
+        let parser_config = ParserConfig {
+            output_dir: output_dir.to_string_lossy().to_string(),
+            ..ParserConfig::new_default()
+        };
+        let mut activity_map = ActivityMap::new(config);
+
+        Parser::new(parser_config)
+            .parse_with(
+                &[PathBuf::from(
+                    "assets/rplace_data_sample/off_diagonal_circles.csv",
+                )],
+                &mut [&mut activity_map],
+            )
+            .unwrap();
+
+        remove_dir_all(&output_dir).unwrap();
+
+        activity_map
+    }
+
+    #[test]
+    fn test_activity_map() {
+        let activity_map = replay(ActivityMapConfig::new_default());
+
+        assert_eq!(activity_map.origin(), (-5, -6));
+        assert_eq!(activity_map.dimensions(), (39, 29));
+        assert_eq!(activity_map.get(ActivityKind::Placements, (-5, -3)), 1);
+        assert_eq!(activity_map.get(ActivityKind::Placements, (11, 4)), 1);
+        assert_eq!(activity_map.get(ActivityKind::Placements, (10, 10)), 0);
+        assert_eq!(activity_map.get(ActivityKind::DistinctUsers, (30, 4)), 1);
+        // The window ends at 13:00:44.101 and the circle was drawn at 13:00:43.658
+        assert_eq!(
+            activity_map.get(ActivityKind::SecondsSinceLastChange, (30, 4)),
+            0
+        );
+        assert_eq!(
+            activity_map.get(ActivityKind::SecondsSinceLastChange, (0, 0)),
+            18
+        );
+
+        let start =
+            NaiveDateTime::parse_from_str("2023-07-20 13:00:30", "%Y-%m-%d %H:%M:%S").unwrap();
+        let activity_map = replay(ActivityMapConfig::new(Some(start), None));
+
+        assert_eq!(activity_map.origin(), (0, -6));
+        assert_eq!(activity_map.get(ActivityKind::Placements, (-5, -3)), 0);
+        assert_eq!(
+            activity_map
+                .values(ActivityKind::Placements)
+                .iter()
+                .sum::<u32>(),
+            29 + 13 + 45
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use image::Rgb;
+
+use crate::pixel_art_scanner::PixelArtMatch;
+
+use super::{
+    parser_image::ParserImage,
+    record::Record,
+    replay_observer::ReplayObserver,
+    user_interner::{UserId, UserInterner},
+};
+
+/// Pixels to attribute, in coordinates of the dataset.
+pub struct AttributionTarget {
+    pub pixels: Vec<(i32, i32)>,
+}
+
+impl AttributionTarget {
+    /// Every pixel between the two corners, both inclusive.
+    pub fn from_rectangle((min_x, min_y): (i32, i32), (max_x, max_y): (i32, i32)) -> Self {
+        let pixels = (min_y..=max_y)
+            .flat_map(|y| (min_x..=max_x).map(move |x| (x, y)))
+            .collect();
+
+        AttributionTarget { pixels }
+    }
+
+    /// Body pixels of a match found in a snapshot whose top left pixel has the given coordinates
+    /// of the dataset, for example `(-1500, -1000)` for the final 2023 canvas.
+    pub fn from_match(found: &PixelArtMatch, origin: (i32, i32)) -> Self {
+        let pixels = found
+            .coordinates
+            .iter()
+            .map(|&(x, y)| (x as i32 + origin.0, y as i32 + origin.1))
+            .collect();
+
+        AttributionTarget { pixels }
+    }
+}
+
+/// Placement on a pixel that survived until the time of the attribution.
+pub struct SurvivingPixel {
+    pub coordinate: (i32, i32),
+    pub user: UserId,
+    pub timestamp: NaiveDateTime,
+    pub color: Rgb<u8>,
+}
+
+pub struct Contributor {
+    pub user: UserId,
+    pub surviving_pixels: u32,
+    pub first_placement: NaiveDateTime,
+    pub last_placement: NaiveDateTime,
+}
+
+/// User who replaced the color a pixel has at the time of the attribution with another one.
+pub struct Destroyer {
+    pub user: UserId,
+    pub attacks: u32,
+    pub first_attack: NaiveDateTime,
+    pub last_attack: NaiveDateTime,
+}
+
+pub struct Attribution {
+    pub surviving_pixels: Vec<SurvivingPixel>,
+    /// Ranked by the number of surviving pixels.
+    pub contributors: Vec<Contributor>,
+    /// Ranked by the number of attacks.
+    pub destroyers: Vec<Destroyer>,
+    /// Pixels nobody placed anything on, so they still have the background color.
+    pub unclaimed_pixels: u32,
+}
+
+struct Placement {
+    user: UserId,
+    timestamp: NaiveDateTime,
+    previous_color: Rgb<u8>,
+    color: Rgb<u8>,
+}
+
+/// Collects the placements on the target pixels up to a point in time, to be used as a
+/// [`ReplayObserver`]. Placements before `since` are only used to find out who placed the
+/// surviving pixels, not to find destroyers.
+pub struct AttributionTracker {
+    since: Option<NaiveDateTime>,
+    at: NaiveDateTime,
+    bounds: ((i32, i32), (i32, i32)),
+    placements: HashMap<(i32, i32), Vec<Placement>>,
+}
+
+impl AttributionTracker {
+    pub fn new(
+        target: AttributionTarget,
+        since: Option<NaiveDateTime>,
+        at: NaiveDateTime,
+    ) -> AttributionTracker {
+        let min_x = target.pixels.iter().map(|&(x, _)| x).min().unwrap_or(0);
+        let min_y = target.pixels.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        let max_x = target.pixels.iter().map(|&(x, _)| x).max().unwrap_or(-1);
+        let max_y = target.pixels.iter().map(|&(_, y)| y).max().unwrap_or(-1);
+
+        AttributionTracker {
+            since,
+            at,
+            bounds: ((min_x, min_y), (max_x, max_y)),
+            placements: target
+                .pixels
+                .into_iter()
+                .map(|pixel| (pixel, vec![]))
+                .collect(),
+        }
+    }
+
+    pub fn attribution(&self) -> Attribution {
+        let mut surviving_pixels = vec![];
+        let mut contributors: HashMap<UserId, Contributor> = HashMap::new();
+        let mut destroyers: HashMap<UserId, Destroyer> = HashMap::new();
+        let mut unclaimed_pixels = 0;
+
+        let mut coordinates: Vec<&(i32, i32)> = self.placements.keys().collect();
+        coordinates.sort_by_key(|&&(x, y)| (y, x));
+
+        for coordinate in coordinates {
+            let placements = &self.placements[coordinate];
+
+            let Some(survivor) = placements.last() else {
+                unclaimed_pixels += 1;
+                continue;
+            };
+
+            surviving_pixels.push(SurvivingPixel {
+                coordinate: *coordinate,
+                user: survivor.user,
+                timestamp: survivor.timestamp,
+                color: survivor.color,
+            });
+
+            let contributor = contributors
+                .entry(survivor.user)
+                .or_insert_with(|| Contributor {
+                    user: survivor.user,
+                    surviving_pixels: 0,
+                    first_placement: survivor.timestamp,
+                    last_placement: survivor.timestamp,
+                });
+            contributor.surviving_pixels += 1;
+            contributor.first_placement = contributor.first_placement.min(survivor.timestamp);
+            contributor.last_placement = contributor.last_placement.max(survivor.timestamp);
+
+            let attacks = placements.iter().filter(|placement| {
+                self.since.is_none_or(|since| placement.timestamp >= since)
+                    && placement.previous_color == survivor.color
+                    && placement.color != survivor.color
+            });
+
+            for attack in attacks {
+                let destroyer = destroyers.entry(attack.user).or_insert_with(|| Destroyer {
+                    user: attack.user,
+                    attacks: 0,
+                    first_attack: attack.timestamp,
+                    last_attack: attack.timestamp,
+                });
+                destroyer.attacks += 1;
+                destroyer.first_attack = destroyer.first_attack.min(attack.timestamp);
+                destroyer.last_attack = destroyer.last_attack.max(attack.timestamp);
+            }
+        }
+
+        let mut contributors: Vec<Contributor> = contributors.into_values().collect();
+        contributors.sort_by(|a, b| {
+            b.surviving_pixels
+                .cmp(&a.surviving_pixels)
+                .then(a.first_placement.cmp(&b.first_placement))
+                .then(a.user.cmp(&b.user))
+        });
+
+        let mut destroyers: Vec<Destroyer> = destroyers.into_values().collect();
+        destroyers.sort_by(|a, b| {
+            b.attacks
+                .cmp(&a.attacks)
+                .then(a.first_attack.cmp(&b.first_attack))
+                .then(a.user.cmp(&b.user))
+        });
+
+        Attribution {
+            surviving_pixels,
+            contributors,
+            destroyers,
+            unclaimed_pixels,
+        }
+    }
+}
+
+impl ReplayObserver for AttributionTracker {
+    fn before_record(&mut self, record: &Record, canvas: &ParserImage, _users: &UserInterner) {
+        if record.timestamp > self.at {
+            return;
+        }
+
+        let ((min_x, min_y), (max_x, max_y)) = record.coordinate.bounds();
+        let ((target_min_x, target_min_y), (target_max_x, target_max_y)) = self.bounds;
+
+        let (min_x, min_y) = (min_x.max(target_min_x), min_y.max(target_min_y));
+        let (max_x, max_y) = (max_x.min(target_max_x), max_y.min(target_max_y));
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if !record.coordinate.contains((x, y)) {
+                    continue;
+                }
+
+                if let Some(placements) = self.placements.get_mut(&(x, y)) {
+                    placements.push(Placement {
+                        user: record.user,
+                        timestamp: record.timestamp,
+                        previous_color: canvas.get_pixel((x, y)),
+                        color: record.pixel_color,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime};
+    use image::Rgb;
+
+    use crate::rplace_data_parser::{
+        Coordinate, ParserImage, Record, ReplayObserver, UserId, UserInterner,
+    };
+
+    use super::{AttributionTarget, AttributionTracker};
+
+    #[test]
+    fn test_attribution() {
+        let start =
+            NaiveDateTime::parse_from_str("2023-07-20 13:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let (red, blue) = (Rgb([255, 69, 0]), Rgb([36, 80, 164]));
+
+        let record =
+            |second: i64, user: u32, coordinate: Coordinate, pixel_color: Rgb<u8>| Record {
+                timestamp: start + Duration::seconds(second),
+                user: UserId(user),
+                coordinate,
+                pixel_color,
+            };
+        let point = |x: i32, y: i32| Coordinate::Point { x, y };
+
+        let records = vec![
+            record(0, 0, point(0, 0), red),
+            record(1, 1, point(0, 0), blue),
+            record(2, 0, point(0, 0), red),
+            record(3, 2, point(1, 0), red),
+            record(4, 1, point(1, 0), blue),
+            record(5, 2, point(1, 0), red),
+            record(6, 4, point(5, 5), blue),
+            record(20, 3, point(1, 0), blue),
+        ];
+
+        let target = AttributionTarget::from_rectangle((0, 0), (2, 0));
+        let mut tracker = AttributionTracker::new(target, None, start + Duration::seconds(10));
+        let mut canvas = ParserImage::new();
+        let users = UserInterner::new();
+
+        for record in &records {
+            tracker.before_record(record, &canvas, &users);
+            canvas.handle_record(record).unwrap();
+        }
+
+        let attribution = tracker.attribution();
+
+        assert_eq!(attribution.unclaimed_pixels, 1);
+        assert_eq!(attribution.surviving_pixels.len(), 2);
+        assert_eq!(attribution.surviving_pixels[1].user, UserId(2));
+        assert_eq!(attribution.surviving_pixels[1].color, red);
+
+        let contributors: Vec<(UserId, u32)> = attribution
+            .contributors
+            .iter()
+            .map(|contributor| (contributor.user, contributor.surviving_pixels))
+            .collect();
+        assert_eq!(contributors, vec![(UserId(0), 1), (UserId(2), 1)]);
+
+        assert_eq!(attribution.destroyers.len(), 1);
+        assert_eq!(attribution.destroyers[0].user, UserId(1));
+        assert_eq!(attribution.destroyers[0].attacks, 2);
+    }
+}
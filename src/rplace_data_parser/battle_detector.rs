@@ -0,0 +1,638 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fs::create_dir_all,
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+use csv::Writer;
+use image::{Rgb, RgbImage};
+use serde::Serialize;
+
+use crate::{config_file::hex_color, image_io::ImageIO};
+
+use super::{
+    parser_image::ParserImage,
+    record::{Coordinate, Record, TIMESTAMP_FORMAT},
+    replay_observer::ReplayObserver,
+    user_interner::{UserId, UserInterner},
+};
+
+type ColorPair = (Rgb<u8>, Rgb<u8>);
+type Cell = (i32, i32);
+/// Colors a pixel had before, each with the time the pixel changed away from it, oldest first.
+type RecentColors = VecDeque<(NaiveDateTime, Rgb<u8>)>;
+
+pub struct BattleDetectorConfig {
+    /// Side of the square cells flips are counted in.
+    pub cell_size: u32,
+    pub window_seconds: u32,
+    /// Flips a cell needs within one window to be part of a battle.
+    pub min_flips: u32,
+    pub frame_padding: u32,
+}
+
+impl BattleDetectorConfig {
+    pub fn new(
+        cell_size: u32,
+        window_seconds: u32,
+        min_flips: u32,
+        frame_padding: u32,
+    ) -> BattleDetectorConfig {
+        BattleDetectorConfig {
+            cell_size,
+            window_seconds,
+            min_flips,
+            frame_padding,
+        }
+    }
+
+    pub fn new_default() -> BattleDetectorConfig {
+        BattleDetectorConfig {
+            cell_size: 8,
+            window_seconds: 60,
+            min_flips: 20,
+            frame_padding: 8,
+        }
+    }
+}
+
+/// Area where pixels kept flipping between colors over consecutive windows.
+pub struct Battle {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// Smallest and largest covered coordinates of the dataset, both inclusive.
+    pub area: ((i32, i32), (i32, i32)),
+    /// Unordered pairs of colors the pixels flipped between, most frequent first.
+    pub color_pairs: Vec<(ColorPair, u32)>,
+    pub participants: u32,
+    pub flips: u32,
+    /// Flips per minute in the busiest window.
+    pub peak_flip_rate: f32,
+    /// Canvas at the end of the busiest window with the contested cells outlined.
+    pub frame: RgbImage,
+}
+
+#[derive(Default)]
+struct CellActivity {
+    flips: u32,
+    users: HashSet<UserId>,
+    color_pairs: HashMap<ColorPair, u32>,
+}
+
+struct BattleState {
+    first_window: i64,
+    last_window: i64,
+    min_cell: Cell,
+    max_cell: Cell,
+    users: HashSet<UserId>,
+    color_pairs: HashMap<ColorPair, u32>,
+    flips: u32,
+    peak_flips: u32,
+    frame: RgbImage,
+    merged_into: Option<usize>,
+}
+
+impl BattleState {
+    fn new(window: i64, cell: Cell) -> BattleState {
+        BattleState {
+            first_window: window,
+            last_window: window,
+            min_cell: cell,
+            max_cell: cell,
+            users: HashSet::new(),
+            color_pairs: HashMap::new(),
+            flips: 0,
+            peak_flips: 0,
+            frame: RgbImage::new(0, 0),
+            merged_into: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BattleRow {
+    index: usize,
+    start: String,
+    end: String,
+    min_x: i32,
+    min_y: i32,
+    max_x: i32,
+    max_y: i32,
+    participants: u32,
+    flips: u32,
+    peak_flip_rate: f32,
+    color_pairs: String,
+}
+
+/// Finds battles in the stream of records, to be used as a [`ReplayObserver`]. Flips are regular
+/// placements that bring a pixel back to a color it had within the last `window_seconds`, like
+/// A to B and back to A, counted per cell and time window. Art drawn once over the canvas doesn't
+/// flip anything. Cells with enough flips that touch each other, in the same or in consecutive
+/// windows, form one battle.
+///
+/// Records are expected in chronological order, a record older than the current window is
+/// counted into the current window.
+pub struct BattleDetector {
+    config: BattleDetectorConfig,
+    first_timestamp: Option<NaiveDateTime>,
+    current_window: Option<i64>,
+    cells: HashMap<Cell, CellActivity>,
+    recent_colors: HashMap<(i32, i32), RecentColors>,
+    active_window: i64,
+    active: HashMap<Cell, usize>,
+    states: Vec<BattleState>,
+    battles: Vec<Battle>,
+}
+
+impl BattleDetector {
+    pub fn new(config: BattleDetectorConfig) -> BattleDetector {
+        BattleDetector {
+            config,
+            first_timestamp: None,
+            current_window: None,
+            cells: HashMap::new(),
+            recent_colors: HashMap::new(),
+            active_window: 0,
+            active: HashMap::new(),
+            states: vec![],
+            battles: vec![],
+        }
+    }
+
+    /// Battles found in the replay, available once it has finished.
+    pub fn battles(&self) -> &[Battle] {
+        &self.battles
+    }
+
+    /// Writes `battles.csv` with one row per battle and the frame of every battle, named after
+    /// its index.
+    pub fn save(&self, output_dir: &str) -> Result<()> {
+        let directory_path = PathBuf::from(output_dir);
+
+        if !directory_path.exists() {
+            create_dir_all(&directory_path)?;
+        }
+
+        let mut writer = Writer::from_path(directory_path.join("battles.csv"))?;
+
+        for (index, battle) in self.battles.iter().enumerate() {
+            let ((min_x, min_y), (max_x, max_y)) = battle.area;
+            let color_pairs: Vec<String> = battle
+                .color_pairs
+                .iter()
+                .map(|((color_a, color_b), count)| {
+                    format!(
+                        "{}/{}:{}",
+                        hex_color::to_hex(color_a),
+                        hex_color::to_hex(color_b),
+                        count
+                    )
+                })
+                .collect();
+
+            writer.serialize(BattleRow {
+                index,
+                start: battle.start.format(TIMESTAMP_FORMAT).to_string(),
+                end: battle.end.format(TIMESTAMP_FORMAT).to_string(),
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+                participants: battle.participants,
+                flips: battle.flips,
+                peak_flip_rate: battle.peak_flip_rate,
+                color_pairs: color_pairs.join(" "),
+            })?;
+
+            ImageIO::save_image(&battle.frame, output_dir, &index.to_string(), ".png")?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn window_duration(&self) -> Duration {
+        Duration::seconds(self.config.window_seconds.max(1) as i64)
+    }
+
+    fn window_of(&mut self, timestamp: NaiveDateTime) -> i64 {
+        let first_timestamp = *self.first_timestamp.get_or_insert(timestamp);
+
+        (timestamp - first_timestamp).num_seconds() / self.config.window_seconds.max(1) as i64
+    }
+
+    fn cell_of(&self, (x, y): (i32, i32)) -> Cell {
+        let cell_size = self.config.cell_size.max(1) as i32;
+
+        (x.div_euclid(cell_size), y.div_euclid(cell_size))
+    }
+
+    fn resolve(&self, mut id: usize) -> usize {
+        while let Some(merged_into) = self.states[id].merged_into {
+            id = merged_into;
+        }
+
+        id
+    }
+
+    /// Turns the hot cells of the finished window into new battles or extends the battles of the
+    /// previous window they touch, merging battles that have grown together.
+    fn finish_window(&mut self, window: i64, canvas: &ParserImage) {
+        let cells = std::mem::take(&mut self.cells);
+        let cutoff = self.window_start(window + 1) - self.window_duration();
+        self.recent_colors
+            .retain(|_, colors| colors.back().is_some_and(|&(changed, _)| changed >= cutoff));
+
+        let hot_cells: HashSet<Cell> = cells
+            .iter()
+            .filter(|(_, activity)| activity.flips >= self.config.min_flips)
+            .map(|(&cell, _)| cell)
+            .collect();
+
+        let previous_active = if self.active_window + 1 == window {
+            std::mem::take(&mut self.active)
+        } else {
+            HashMap::new()
+        };
+        self.active.clear();
+        self.active_window = window;
+
+        for component in connected_cells(&hot_cells) {
+            let touched: BTreeSet<usize> = component
+                .iter()
+                .flat_map(|&(x, y)| NEIGHBOUR_OFFSETS.map(|(dx, dy)| (x + dx, y + dy)))
+                .filter_map(|cell| previous_active.get(&cell))
+                .map(|&id| self.resolve(id))
+                .collect();
+
+            let id = match touched.first() {
+                Some(&id) => {
+                    for &other in touched.iter().skip(1) {
+                        self.merge(other, id);
+                    }
+                    id
+                }
+                None => {
+                    self.states.push(BattleState::new(window, component[0]));
+                    self.states.len() - 1
+                }
+            };
+
+            let mut window_flips = 0;
+            let state = &mut self.states[id];
+
+            for cell in &component {
+                let activity = &cells[cell];
+
+                window_flips += activity.flips;
+                state.users.extend(&activity.users);
+                for (&pair, &count) in &activity.color_pairs {
+                    *state.color_pairs.entry(pair).or_default() += count;
+                }
+
+                state.min_cell = (state.min_cell.0.min(cell.0), state.min_cell.1.min(cell.1));
+                state.max_cell = (state.max_cell.0.max(cell.0), state.max_cell.1.max(cell.1));
+            }
+
+            state.flips += window_flips;
+            state.last_window = window;
+
+            if window_flips > state.peak_flips {
+                state.peak_flips = window_flips;
+                self.states[id].frame = self.render_frame(id, &component, canvas);
+            }
+
+            for cell in component {
+                self.active.insert(cell, id);
+            }
+        }
+    }
+
+    fn merge(&mut self, from: usize, into: usize) {
+        let from_state = std::mem::replace(
+            &mut self.states[from],
+            BattleState {
+                merged_into: Some(into),
+                ..BattleState::new(0, (0, 0))
+            },
+        );
+        let state = &mut self.states[into];
+
+        state.first_window = state.first_window.min(from_state.first_window);
+        state.min_cell = (
+            state.min_cell.0.min(from_state.min_cell.0),
+            state.min_cell.1.min(from_state.min_cell.1),
+        );
+        state.max_cell = (
+            state.max_cell.0.max(from_state.max_cell.0),
+            state.max_cell.1.max(from_state.max_cell.1),
+        );
+        state.users.extend(from_state.users);
+        for (pair, count) in from_state.color_pairs {
+            *state.color_pairs.entry(pair).or_default() += count;
+        }
+        state.flips += from_state.flips;
+
+        if from_state.peak_flips > state.peak_flips {
+            state.peak_flips = from_state.peak_flips;
+            state.frame = from_state.frame;
+        }
+    }
+
+    fn area_of(&self, state: &BattleState) -> ((i32, i32), (i32, i32)) {
+        let cell_size = self.config.cell_size.max(1) as i32;
+
+        (
+            (state.min_cell.0 * cell_size, state.min_cell.1 * cell_size),
+            (
+                (state.max_cell.0 + 1) * cell_size - 1,
+                (state.max_cell.1 + 1) * cell_size - 1,
+            ),
+        )
+    }
+
+    fn render_frame(&self, id: usize, component: &[Cell], canvas: &ParserImage) -> RgbImage {
+        let ((min_x, min_y), (max_x, max_y)) = self.area_of(&self.states[id]);
+        let padding = self.config.frame_padding as i32;
+        let cell_size = self.config.cell_size.max(1) as i32;
+        let (left, top) = (min_x - padding, min_y - padding);
+
+        let mut frame = canvas.crop((left, top), (max_x + padding, max_y + padding));
+        let contested: HashSet<Cell> = component.iter().copied().collect();
+
+        for &(cell_x, cell_y) in component {
+            for offset in 0..cell_size {
+                let edges = [
+                    ((0, -1), (offset, 0)),
+                    ((0, 1), (offset, cell_size - 1)),
+                    ((-1, 0), (0, offset)),
+                    ((1, 0), (cell_size - 1, offset)),
+                ];
+
+                for ((dx, dy), (x, y)) in edges {
+                    if contested.contains(&(cell_x + dx, cell_y + dy)) {
+                        continue;
+                    }
+
+                    let x = (cell_x * cell_size + x - left) as u32;
+                    let y = (cell_y * cell_size + y - top) as u32;
+                    frame.put_pixel(x, y, OUTLINE_COLOR);
+                }
+            }
+        }
+
+        frame
+    }
+
+    fn window_start(&self, window: i64) -> NaiveDateTime {
+        self.first_timestamp.unwrap_or_default()
+            + Duration::seconds(window * self.config.window_seconds.max(1) as i64)
+    }
+}
+
+impl ReplayObserver for BattleDetector {
+    fn before_record(&mut self, record: &Record, canvas: &ParserImage, _users: &UserInterner) {
+        let window = self.window_of(record.timestamp);
+
+        match self.current_window {
+            Some(current_window) if window > current_window => {
+                self.finish_window(current_window, canvas);
+                self.current_window = Some(window);
+            }
+            Some(_) => {}
+            None => self.current_window = Some(window),
+        }
+
+        let Coordinate::Point { x, y } = record.coordinate else {
+            return;
+        };
+
+        let previous_color = canvas.get_pixel((x, y));
+
+        if previous_color == record.pixel_color {
+            return;
+        }
+
+        let since = record.timestamp - self.window_duration();
+        let recent_colors = self.recent_colors.entry((x, y)).or_default();
+
+        while recent_colors
+            .front()
+            .is_some_and(|&(changed, _)| changed < since)
+        {
+            recent_colors.pop_front();
+        }
+
+        let flipped_back = recent_colors
+            .iter()
+            .any(|&(_, color)| color == record.pixel_color);
+
+        recent_colors.retain(|&(_, color)| color != previous_color);
+        recent_colors.push_back((record.timestamp, previous_color));
+
+        if !flipped_back {
+            return;
+        }
+
+        let pair = if previous_color.0 <= record.pixel_color.0 {
+            (previous_color, record.pixel_color)
+        } else {
+            (record.pixel_color, previous_color)
+        };
+
+        let cell = self.cell_of((x, y));
+        let activity = self.cells.entry(cell).or_default();
+
+        activity.flips += 1;
+        activity.users.insert(record.user);
+        *activity.color_pairs.entry(pair).or_default() += 1;
+    }
+
+    fn finish(&mut self, canvas: &ParserImage, _users: &UserInterner) {
+        if let Some(current_window) = self.current_window.take() {
+            self.finish_window(current_window, canvas);
+        }
+
+        let window_minutes = self.config.window_seconds.max(1) as f32 / 60.0;
+
+        self.battles = self
+            .states
+            .iter()
+            .filter(|state| state.merged_into.is_none())
+            .map(|state| {
+                let mut color_pairs: Vec<(ColorPair, u32)> =
+                    state.color_pairs.iter().map(|(&p, &c)| (p, c)).collect();
+                color_pairs.sort_by(|((a1, a2), count_a), ((b1, b2), count_b)| {
+                    count_b.cmp(count_a).then((a1.0, a2.0).cmp(&(b1.0, b2.0)))
+                });
+
+                Battle {
+                    start: self.window_start(state.first_window),
+                    end: self.window_start(state.last_window + 1),
+                    area: self.area_of(state),
+                    color_pairs,
+                    participants: state.users.len() as u32,
+                    flips: state.flips,
+                    peak_flip_rate: state.peak_flips as f32 / window_minutes,
+                    frame: state.frame.clone(),
+                }
+            })
+            .collect();
+    }
+}
+
+/// Groups the cells into sets of cells touching each other, also diagonally.
+fn connected_cells(cells: &HashSet<Cell>) -> Vec<Vec<Cell>> {
+    let mut sorted_cells: Vec<Cell> = cells.iter().copied().collect();
+    sorted_cells.sort();
+
+    let mut visited: HashSet<Cell> = HashSet::new();
+    let mut components = vec![];
+
+    for start in sorted_cells {
+        if !visited.insert(start) {
+            continue;
+        }
+
+        let mut component = vec![];
+        let mut queue = VecDeque::from([start]);
+
+        while let Some((x, y)) = queue.pop_front() {
+            component.push((x, y));
+
+            for (dx, dy) in NEIGHBOUR_OFFSETS {
+                let neighbour = (x + dx, y + dy);
+
+                if cells.contains(&neighbour) && visited.insert(neighbour) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        component.sort();
+        components.push(component);
+    }
+
+    components
+}
+
+const NEIGHBOUR_OFFSETS: [(i32, i32); 9] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (0, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+const OUTLINE_COLOR: Rgb<u8> = Rgb([255, 0, 0]);
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime};
+    use image::Rgb;
+
+    use crate::rplace_data_parser::{
+        Coordinate, ParserImage, Record, ReplayObserver, UserId, UserInterner,
+    };
+
+    use super::{BattleDetector, BattleDetectorConfig};
+
+    #[test]
+    fn test_detect_battles() {
+        let start =
+            NaiveDateTime::parse_from_str("2023-07-20 13:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let (red, blue) = (Rgb([255, 69, 0]), Rgb([36, 80, 164]));
+
+        let record = |second: i64, user: u32, (x, y): (i32, i32), pixel_color: Rgb<u8>| Record {
+            timestamp: start + Duration::seconds(second),
+            user: UserId(user),
+            coordinate: Coordinate::Point { x, y },
+            pixel_color,
+        };
+
+        let mut records = vec![];
+        for second in 0..30 {
+            let (user, color) = if second % 2 == 0 { (0, red) } else { (1, blue) };
+            records.push(record(second, user, (3, 3), color));
+        }
+        // A few changes elsewhere are not a battle
+        for second in 30..33 {
+            records.push(record(second, 2, (100, 100), red));
+        }
+        for second in 50..70 {
+            let color = if second % 2 == 0 { red } else { blue };
+            records.push(record(second, 3, (40 + second as i32 % 3, 41), color));
+        }
+
+        let mut canvas = ParserImage::new();
+        let users = UserInterner::new();
+        let mut detector = BattleDetector::new(BattleDetectorConfig::new(4, 10, 5, 2));
+
+        for record in &records {
+            detector.before_record(record, &canvas, &users);
+            canvas.handle_record(record).unwrap();
+            detector.after_record(record, &canvas, &users);
+        }
+        detector.finish(&canvas, &users);
+
+        let battles = detector.battles();
+        assert_eq!(battles.len(), 2);
+
+        let first = &battles[0];
+        assert_eq!(first.start, start);
+        assert_eq!(first.end, start + Duration::seconds(30));
+        assert_eq!(first.area, ((0, 0), (3, 3)));
+        assert_eq!(first.participants, 2);
+        // Painting red over white and blue over red doesn't flip anything yet
+        assert_eq!(first.flips, 28);
+        assert_eq!(first.color_pairs, vec![((blue, red), 28)]);
+        assert_eq!(first.peak_flip_rate, 60.0);
+        assert_eq!(first.frame.dimensions(), (8, 8));
+        assert_eq!(*first.frame.get_pixel(2, 2), Rgb([255, 0, 0]));
+
+        let second = &battles[1];
+        assert_eq!(second.area, ((40, 40), (43, 43)));
+        assert_eq!(second.participants, 1);
+    }
+
+    #[test]
+    fn test_ignore_art_drawn_once() {
+        let start =
+            NaiveDateTime::parse_from_str("2023-07-20 13:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let (red, blue) = (Rgb([255, 69, 0]), Rgb([36, 80, 164]));
+
+        // A red square drawn over white, then a blue one drawn over it
+        let mut records = vec![];
+        for (index, color) in [red, blue].into_iter().enumerate() {
+            for y in 0..8 {
+                for x in 0..8 {
+                    records.push(Record {
+                        timestamp: start + Duration::seconds(index as i64 * 8 + y as i64),
+                        user: UserId(x as u32),
+                        coordinate: Coordinate::Point { x, y },
+                        pixel_color: color,
+                    });
+                }
+            }
+        }
+
+        let mut canvas = ParserImage::new();
+        let users = UserInterner::new();
+        let mut detector = BattleDetector::new(BattleDetectorConfig::new(4, 60, 5, 2));
+
+        for record in &records {
+            detector.before_record(record, &canvas, &users);
+            canvas.handle_record(record).unwrap();
+            detector.after_record(record, &canvas, &users);
+        }
+        detector.finish(&canvas, &users);
+
+        assert!(detector.battles().is_empty());
+    }
+}
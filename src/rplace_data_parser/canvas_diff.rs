@@ -0,0 +1,332 @@
+use std::{collections::HashMap, fs::create_dir_all, path::PathBuf};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use csv::Writer;
+use image::{Rgb, RgbImage};
+use serde::Serialize;
+
+use crate::{config_file::hex_color, image_io::ImageIO};
+
+use super::{
+    parser_image::ParserImage, record::Record, replay_observer::ReplayObserver,
+    user_interner::UserInterner,
+};
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PixelChange {
+    pub x: i32,
+    pub y: i32,
+    #[serde(serialize_with = "hex_color::serialize")]
+    pub old_color: Rgb<u8>,
+    #[serde(serialize_with = "hex_color::serialize")]
+    pub new_color: Rgb<u8>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ColorChangeStats {
+    #[serde(serialize_with = "hex_color::serialize")]
+    pub color: Rgb<u8>,
+    /// Pixels that have the color at the end but didn't at the start.
+    pub gained: u32,
+    /// Pixels that had the color at the start but don't at the end.
+    pub lost: u32,
+}
+
+pub struct CanvasDiff {
+    /// Changed pixels in coordinates of the dataset, row after row.
+    pub changes: Vec<PixelChange>,
+    /// Ordered by the number of pixels gained and lost.
+    pub color_stats: Vec<ColorChangeStats>,
+    /// Coordinates of the dataset the top left pixel of the image belongs to.
+    pub origin: (i32, i32),
+    /// Canvas at the end with unchanged pixels greyed out and changed pixels in their new color.
+    pub image: RgbImage,
+}
+
+impl CanvasDiff {
+    /// Compares the colors pixels had at the start to the canvas at the end. Only pixels in
+    /// `start_colors` can have changed, any other pixel is the same at both points in time.
+    pub fn new(
+        start_bounds: ((i32, i32), (i32, i32)),
+        start_colors: &HashMap<(i32, i32), Rgb<u8>>,
+        end: &ParserImage,
+    ) -> CanvasDiff {
+        let ((min_x, min_y), (max_x, max_y)) = union_bounds(start_bounds, canvas_bounds(end));
+        let width = (max_x - min_x + 1).max(0) as u32;
+        let height = (max_y - min_y + 1).max(0) as u32;
+
+        let mut changes = vec![];
+        let mut color_stats: HashMap<Rgb<u8>, ColorChangeStats> = HashMap::new();
+        let mut image = RgbImage::new(width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let coordinate = (min_x + x as i32, min_y + y as i32);
+                let new_color = end.get_pixel(coordinate);
+
+                let old_color = match start_colors.get(&coordinate) {
+                    Some(&old_color) if old_color != new_color => old_color,
+                    _ => {
+                        image.put_pixel(x, y, greyed(new_color));
+                        continue;
+                    }
+                };
+
+                image.put_pixel(x, y, new_color);
+                for (color, gained) in [(old_color, false), (new_color, true)] {
+                    let stats = color_stats.entry(color).or_insert(ColorChangeStats {
+                        color,
+                        gained: 0,
+                        lost: 0,
+                    });
+
+                    if gained {
+                        stats.gained += 1;
+                    } else {
+                        stats.lost += 1;
+                    }
+                }
+
+                changes.push(PixelChange {
+                    x: coordinate.0,
+                    y: coordinate.1,
+                    old_color,
+                    new_color,
+                });
+            }
+        }
+
+        let mut color_stats: Vec<ColorChangeStats> = color_stats.into_values().collect();
+        color_stats.sort_by(|a, b| {
+            (b.gained + b.lost)
+                .cmp(&(a.gained + a.lost))
+                .then(a.color.0.cmp(&b.color.0))
+        });
+
+        CanvasDiff {
+            changes,
+            color_stats,
+            origin: (min_x, min_y),
+            image,
+        }
+    }
+
+    /// Writes `changes.csv`, `color_stats.csv` and `diff.png`.
+    pub fn save(&self, output_dir: &str) -> Result<()> {
+        let directory_path = PathBuf::from(output_dir);
+
+        if !directory_path.exists() {
+            create_dir_all(&directory_path)?;
+        }
+
+        let mut writer = Writer::from_path(directory_path.join("changes.csv"))?;
+        for change in &self.changes {
+            writer.serialize(change)?;
+        }
+        writer.flush()?;
+
+        let mut writer = Writer::from_path(directory_path.join("color_stats.csv"))?;
+        for stats in &self.color_stats {
+            writer.serialize(stats)?;
+        }
+        writer.flush()?;
+
+        ImageIO::save_image(&self.image, output_dir, "diff", ".png")?;
+
+        Ok(())
+    }
+}
+
+/// Compares the canvas at two points in time, to be used as a [`ReplayObserver`]. Instead of
+/// keeping the whole canvas at the start, only the colors of pixels drawn on between both points
+/// in time are kept, from right before they were first drawn on. A point in time after the last
+/// record gets the final canvas.
+pub struct CanvasDiffTracker {
+    start_time: NaiveDateTime,
+    end_time: NaiveDateTime,
+    start_bounds: Option<((i32, i32), (i32, i32))>,
+    start_colors: HashMap<(i32, i32), Rgb<u8>>,
+    diff: Option<CanvasDiff>,
+}
+
+impl CanvasDiffTracker {
+    pub fn new(start_time: NaiveDateTime, end_time: NaiveDateTime) -> CanvasDiffTracker {
+        CanvasDiffTracker {
+            start_time,
+            end_time,
+            start_bounds: None,
+            start_colors: HashMap::new(),
+            diff: None,
+        }
+    }
+
+    /// Difference between the two points in time, available once the replay has finished.
+    pub fn diff(&self) -> Option<&CanvasDiff> {
+        self.diff.as_ref()
+    }
+
+    fn finish_diff(&mut self, canvas: &ParserImage) {
+        let start_bounds = *self
+            .start_bounds
+            .get_or_insert_with(|| canvas_bounds(canvas));
+
+        self.diff = Some(CanvasDiff::new(start_bounds, &self.start_colors, canvas));
+        self.start_colors = HashMap::new();
+    }
+}
+
+impl ReplayObserver for CanvasDiffTracker {
+    fn before_record(&mut self, record: &Record, canvas: &ParserImage, _users: &UserInterner) {
+        if self.diff.is_some() {
+            return;
+        }
+        if self.start_bounds.is_none() && record.timestamp > self.start_time {
+            self.start_bounds = Some(canvas_bounds(canvas));
+        }
+        if record.timestamp > self.end_time {
+            self.finish_diff(canvas);
+            return;
+        }
+        if self.start_bounds.is_none() {
+            return;
+        }
+
+        let ((min_x, min_y), (max_x, max_y)) = record.coordinate.bounds();
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if record.coordinate.contains((x, y)) {
+                    self.start_colors
+                        .entry((x, y))
+                        .or_insert_with(|| canvas.get_pixel((x, y)));
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, canvas: &ParserImage, _users: &UserInterner) {
+        if self.diff.is_none() {
+            self.finish_diff(canvas);
+        }
+    }
+}
+
+/// Smallest and largest coordinates of the dataset covered by the canvas, both inclusive.
+fn canvas_bounds(canvas: &ParserImage) -> ((i32, i32), (i32, i32)) {
+    let (x, y) = canvas.origin();
+    let (width, height) = canvas.dimensions();
+
+    ((x, y), (x + width as i32 - 1, y + height as i32 - 1))
+}
+
+fn union_bounds(
+    ((min_x1, min_y1), (max_x1, max_y1)): ((i32, i32), (i32, i32)),
+    ((min_x2, min_y2), (max_x2, max_y2)): ((i32, i32), (i32, i32)),
+) -> ((i32, i32), (i32, i32)) {
+    (
+        (min_x1.min(min_x2), min_y1.min(min_y2)),
+        (max_x1.max(max_x2), max_y1.max(max_y2)),
+    )
+}
+
+fn greyed(color: Rgb<u8>) -> Rgb<u8> {
+    let Rgb([r, g, b]) = color;
+    let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+    let value = (GREY_BASE + luma * GREY_CONTRAST).round() as u8;
+
+    Rgb([value, value, value])
+}
+
+const GREY_BASE: f32 = 160.0;
+const GREY_CONTRAST: f32 = 0.3;
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime};
+    use image::Rgb;
+
+    use crate::rplace_data_parser::{
+        Coordinate, ParserImage, Record, ReplayObserver, UserId, UserInterner,
+    };
+
+    use super::{CanvasDiffTracker, ColorChangeStats, PixelChange};
+
+    #[test]
+    fn test_canvas_diff() {
+        let start =
+            NaiveDateTime::parse_from_str("2023-07-20 13:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let (white, red, blue) = (Rgb([255, 255, 255]), Rgb([255, 69, 0]), Rgb([36, 80, 164]));
+
+        let record = |second: i64, (x, y): (i32, i32), pixel_color: Rgb<u8>| Record {
+            timestamp: start + Duration::seconds(second),
+            user: UserId(0),
+            coordinate: Coordinate::Point { x, y },
+            pixel_color,
+        };
+
+        let records = vec![
+            record(0, (0, 0), red),
+            record(1, (1, 0), red),
+            record(6, (0, 0), blue),
+            record(7, (1, 0), blue),
+            record(8, (1, 0), red),
+            record(9, (2, 1), blue),
+            record(12, (0, 0), red),
+        ];
+
+        let mut tracker =
+            CanvasDiffTracker::new(start + Duration::seconds(5), start + Duration::seconds(10));
+        let mut canvas = ParserImage::new();
+        let users = UserInterner::new();
+
+        for record in &records {
+            tracker.before_record(record, &canvas, &users);
+            canvas.handle_record(record).unwrap();
+        }
+        tracker.finish(&canvas, &users);
+
+        let diff = tracker.diff().unwrap();
+
+        assert_eq!(
+            diff.changes,
+            vec![
+                PixelChange {
+                    x: 0,
+                    y: 0,
+                    old_color: red,
+                    new_color: blue
+                },
+                PixelChange {
+                    x: 2,
+                    y: 1,
+                    old_color: white,
+                    new_color: blue
+                },
+            ]
+        );
+        assert_eq!(
+            diff.color_stats,
+            vec![
+                ColorChangeStats {
+                    color: blue,
+                    gained: 2,
+                    lost: 0
+                },
+                ColorChangeStats {
+                    color: red,
+                    gained: 0,
+                    lost: 1
+                },
+                ColorChangeStats {
+                    color: white,
+                    gained: 0,
+                    lost: 1
+                },
+            ]
+        );
+        assert_eq!(diff.image.dimensions(), (3, 2));
+        assert_eq!(*diff.image.get_pixel(0, 0), blue);
+        assert_ne!(*diff.image.get_pixel(1, 0), red);
+    }
+}
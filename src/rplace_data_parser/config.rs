@@ -1,14 +1,33 @@
+use core::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config_file::{ConfigFile, ValidationErrors};
+
+use super::record_filter::FilterConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum OnError {
     Stop,
     Print,
     Nothing,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ParserConfig {
     pub verbose: bool,
     pub on_error: OnError,
     pub output_dir: String,
     pub save_interval_seconds: u32,
+    /// Files parsed in parallel before their records are merged and replayed, files are expected
+    /// in chronological order.
+    pub batch_files: u32,
+    #[serde(default)]
+    pub filter: FilterConfig,
 }
 
 impl ParserConfig {
@@ -23,6 +42,8 @@ impl ParserConfig {
             output_dir,
             on_error,
             save_interval_seconds,
+            batch_files: 2,
+            filter: FilterConfig::new_default(),
         }
     }
 
@@ -32,6 +53,148 @@ impl ParserConfig {
             output_dir: String::from("output/output_images"),
             on_error: OnError::Print,
             save_interval_seconds: 10000,
+            batch_files: 2,
+            filter: FilterConfig::new_default(),
+        }
+    }
+
+    /// Named presets: `default`, `strict` which stops on the first invalid record and `lenient`
+    /// which silently skips invalid records.
+    pub fn preset(name: &str) -> Option<ParserConfig> {
+        match name {
+            "default" => Some(ParserConfig::new_default()),
+            "strict" => Some(ParserConfig {
+                on_error: OnError::Stop,
+                ..ParserConfig::new_default()
+            }),
+            "lenient" => Some(ParserConfig {
+                on_error: OnError::Nothing,
+                ..ParserConfig::new_default()
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn load(path: &PathBuf) -> Result<ParserConfig> {
+        let config: ParserConfig = ConfigFile::load(path, ParserConfig::preset)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationErrors<ParserConfigError>> {
+        let mut problems = vec![];
+
+        if self.save_interval_seconds == 0 {
+            problems.push(ParserConfigError::ZeroSaveInterval);
+        }
+
+        if self.batch_files == 0 {
+            problems.push(ParserConfigError::ZeroBatchFiles);
+        }
+
+        if self.output_dir.trim().is_empty() {
+            problems.push(ParserConfigError::EmptyOutputDir);
+        } else if Path::new(&self.output_dir).is_file() {
+            problems.push(ParserConfigError::OutputDirIsFile(self.output_dir.clone()));
+        }
+
+        if let (Some(start), Some(end)) = (self.filter.start, self.filter.end) {
+            if start > end {
+                problems.push(ParserConfigError::InvalidTimeRange);
+            }
         }
+
+        if let Some([x1, y1, x2, y2]) = self.filter.bounding_box {
+            if x1 > x2 || y1 > y2 {
+                problems.push(ParserConfigError::InvalidBoundingBox);
+            }
+        }
+
+        for path in [
+            &self.filter.allowed_users_file,
+            &self.filter.denied_users_file,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if !Path::new(path).is_file() {
+                problems.push(ParserConfigError::MissingUsersFile(path.clone()));
+            }
+        }
+
+        ValidationErrors::check(problems)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParserConfigError {
+    ZeroSaveInterval,
+    ZeroBatchFiles,
+    EmptyOutputDir,
+    OutputDirIsFile(String),
+    InvalidTimeRange,
+    InvalidBoundingBox,
+    MissingUsersFile(String),
+}
+
+impl fmt::Display for ParserConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParserConfigError::ZeroSaveInterval => {
+                write!(f, "save_interval_seconds has to be at least 1")
+            }
+            ParserConfigError::ZeroBatchFiles => write!(f, "batch_files has to be at least 1"),
+            ParserConfigError::EmptyOutputDir => write!(f, "output_dir can't be empty"),
+            ParserConfigError::OutputDirIsFile(output_dir) => {
+                write!(f, "output_dir {:?} is an existing file", output_dir)
+            }
+            ParserConfigError::InvalidTimeRange => {
+                write!(f, "filter.start is later than filter.end")
+            }
+            ParserConfigError::InvalidBoundingBox => write!(
+                f,
+                "filter.bounding_box has to be [x1, y1, x2, y2] with x1 <= x2 and y1 <= y2"
+            ),
+            ParserConfigError::MissingUsersFile(path) => {
+                write!(f, "users file {:?} doesn't exist", path)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParserConfig, ParserConfigError};
+
+    #[test]
+    fn test_validate_parser_config() {
+        assert!(ParserConfig::new_default().validate().is_ok());
+
+        let config = ParserConfig {
+            output_dir: String::from(" "),
+            save_interval_seconds: 0,
+            ..ParserConfig::new_default()
+        };
+
+        assert_eq!(
+            config.validate().unwrap_err().problems,
+            vec![
+                ParserConfigError::ZeroSaveInterval,
+                ParserConfigError::EmptyOutputDir
+            ]
+        );
+
+        let config = ParserConfig {
+            output_dir: String::from("Cargo.toml"),
+            ..ParserConfig::new_default()
+        };
+
+        assert_eq!(
+            config.validate().unwrap_err().problems,
+            vec![ParserConfigError::OutputDirIsFile(String::from(
+                "Cargo.toml"
+            ))]
+        );
     }
 }
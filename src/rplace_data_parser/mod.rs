@@ -1,7 +1,37 @@
+mod activity_map;
+mod attribution;
+mod battle_detector;
+mod canvas_diff;
 mod config;
+mod moderation_log;
+mod palette;
+mod parallel_reader;
 mod parser;
 mod parser_image;
 mod record;
+mod record_decoder;
+mod record_exporter;
+mod record_filter;
+mod replay_observer;
+mod user_interner;
 
-pub use config::ParserConfig;
+pub use activity_map::{ActivityKind, ActivityMap, ActivityMapConfig};
+pub use attribution::{
+    Attribution, AttributionTarget, AttributionTracker, Contributor, Destroyer, SurvivingPixel,
+};
+pub use battle_detector::{Battle, BattleDetector, BattleDetectorConfig};
+pub use canvas_diff::{CanvasDiff, CanvasDiffTracker, ColorChangeStats, PixelChange};
+pub use config::{OnError, ParserConfig, ParserConfigError};
+pub use moderation_log::{ModerationEvent, ModerationLog};
+pub use palette::{Palette, PaletteError};
 pub use parser::Parser;
+pub use parser_image::ParserImage;
+pub use record::{Coordinate, RawRecord, Record, Shape};
+pub use record_decoder::RecordDecodeError;
+pub use record_exporter::RecordExporter;
+pub use record_filter::{
+    BoundingBoxFilter, ColorFilter, FilterConfig, RecordFilter, ShapeFilter, TimeRangeFilter,
+    UserDecisions, UserFilter,
+};
+pub use replay_observer::ReplayObserver;
+pub use user_interner::{UserId, UserInterner, UserInternerError};
@@ -0,0 +1,244 @@
+use std::{collections::HashMap, fs::create_dir_all, path::PathBuf};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use csv::Writer;
+use image::{Rgb, RgbImage};
+use serde::Serialize;
+
+use crate::{config_file::hex_color, image_io::ImageIO};
+
+use super::{
+    parser_image::ParserImage,
+    record::{Coordinate, Record, TIMESTAMP_FORMAT},
+    replay_observer::ReplayObserver,
+    user_interner::{UserId, UserInterner},
+};
+
+/// Rectangle or circle drawn by an admin.
+pub struct ModerationEvent {
+    pub timestamp: NaiveDateTime,
+    pub user: UserId,
+    pub coordinate: Coordinate,
+    pub color: Rgb<u8>,
+    /// Colors the affected pixels had before the event with their pixel counts, most common first.
+    pub prior_colors: Vec<(Rgb<u8>, u32)>,
+    /// Affected region with the padding around it, before and after the event.
+    pub before: RgbImage,
+    pub after: RgbImage,
+}
+
+impl ModerationEvent {
+    pub fn affected_pixels(&self) -> u32 {
+        self.prior_colors.iter().map(|&(_, count)| count).sum()
+    }
+}
+
+#[derive(Serialize)]
+struct ModerationEventRow {
+    index: usize,
+    timestamp: String,
+    user_id: u32,
+    coordinate: String,
+    #[serde(serialize_with = "hex_color::serialize")]
+    color: Rgb<u8>,
+    affected_pixels: u32,
+    prior_colors: String,
+}
+
+/// Part of an event that has to be captured before the record is drawn.
+struct PendingEvent {
+    prior_colors: Vec<(Rgb<u8>, u32)>,
+    before: RgbImage,
+}
+
+/// Collects the moderation events of a replay, to be used as a [`ReplayObserver`].
+pub struct ModerationLog {
+    crop_padding: u32,
+    events: Vec<ModerationEvent>,
+    pending: Option<PendingEvent>,
+}
+
+impl ModerationLog {
+    pub fn new(crop_padding: u32) -> ModerationLog {
+        ModerationLog {
+            crop_padding,
+            events: vec![],
+            pending: None,
+        }
+    }
+
+    pub fn events(&self) -> &[ModerationEvent] {
+        &self.events
+    }
+
+    /// Writes `moderation_events.csv` with one row per event and the before and after crops of
+    /// every event, named after its index in the log. Users are written as their ids, the table
+    /// of the parser resolves them.
+    pub fn save(&self, output_dir: &str) -> Result<()> {
+        let directory_path = PathBuf::from(output_dir);
+
+        if !directory_path.exists() {
+            create_dir_all(&directory_path)?;
+        }
+
+        let mut writer = Writer::from_path(directory_path.join("moderation_events.csv"))?;
+
+        for (index, event) in self.events.iter().enumerate() {
+            let prior_colors: Vec<String> = event
+                .prior_colors
+                .iter()
+                .map(|(color, count)| format!("{}:{}", hex_color::to_hex(color), count))
+                .collect();
+
+            writer.serialize(ModerationEventRow {
+                index,
+                timestamp: event.timestamp.format(TIMESTAMP_FORMAT).to_string(),
+                user_id: event.user.0,
+                coordinate: event.coordinate.to_string(),
+                color: event.color,
+                affected_pixels: event.affected_pixels(),
+                prior_colors: prior_colors.join(" "),
+            })?;
+
+            ImageIO::save_image(
+                &event.before,
+                output_dir,
+                &format!("{}_before", index),
+                ".png",
+            )?;
+            ImageIO::save_image(
+                &event.after,
+                output_dir,
+                &format!("{}_after", index),
+                ".png",
+            )?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn crop(&self, coordinate: &Coordinate, canvas: &ParserImage) -> RgbImage {
+        let ((min_x, min_y), (max_x, max_y)) = coordinate.bounds();
+        let padding = self.crop_padding as i32;
+
+        canvas.crop(
+            (min_x - padding, min_y - padding),
+            (max_x + padding, max_y + padding),
+        )
+    }
+}
+
+impl ReplayObserver for ModerationLog {
+    fn before_record(&mut self, record: &Record, canvas: &ParserImage, _users: &UserInterner) {
+        if !record.coordinate.is_moderation() {
+            return;
+        }
+
+        let ((min_x, min_y), (max_x, max_y)) = record.coordinate.bounds();
+        let mut color_counts: HashMap<Rgb<u8>, u32> = HashMap::new();
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if record.coordinate.contains((x, y)) {
+                    *color_counts.entry(canvas.get_pixel((x, y))).or_default() += 1;
+                }
+            }
+        }
+
+        let mut prior_colors: Vec<(Rgb<u8>, u32)> = color_counts.into_iter().collect();
+        prior_colors.sort_by(|(color_a, count_a), (color_b, count_b)| {
+            count_b.cmp(count_a).then(color_a.0.cmp(&color_b.0))
+        });
+
+        self.pending = Some(PendingEvent {
+            prior_colors,
+            before: self.crop(&record.coordinate, canvas),
+        });
+    }
+
+    fn after_record(&mut self, record: &Record, canvas: &ParserImage, _users: &UserInterner) {
+        let Some(PendingEvent {
+            prior_colors,
+            before,
+        }) = self.pending.take()
+        else {
+            return;
+        };
+
+        self.events.push(ModerationEvent {
+            timestamp: record.timestamp,
+            user: record.user,
+            coordinate: record.coordinate,
+            color: record.pixel_color,
+            prior_colors,
+            before,
+            after: self.crop(&record.coordinate, canvas),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::remove_dir_all, path::PathBuf};
+
+    use image::Rgb;
+
+    use crate::rplace_data_parser::{Coordinate, Parser, ParserConfig};
+
+    use super::ModerationLog;
+
+    #[test]
+    fn test_moderation_log() {
+        let output_dir = std::env::temp_dir().join(format!(
+            "pixel_crab_test_moderation_log_{}",
+            std::process::id()
+        ));
+        let config = ParserConfig {
+            output_dir: output_dir.to_string_lossy().to_string(),
+            ..ParserConfig::new_default()
+        };
+        let mut moderation_log = ModerationLog::new(2);
+        let mut parser = Parser::new(config);
+
+        parser
+            .parse_with(
+                &[PathBuf::from(
+                    "assets/rplace_data_sample/different_forms_of_coordinates.csv",
+                )],
+                &mut [&mut moderation_log],
+            )
+            .unwrap();
+
+        let events = moderation_log.events();
+        assert_eq!(events.len(), 2);
+
+        let rectangle = &events[0];
+        assert_eq!(
+            rectangle.coordinate,
+            Coordinate::Rectangle {
+                x1: 0,
+                y1: 0,
+                x2: 5,
+                y2: 5
+            }
+        );
+        assert_eq!(rectangle.prior_colors, vec![(Rgb([255, 255, 255]), 36)]);
+        assert_eq!(rectangle.before.dimensions(), (10, 10));
+        assert_eq!(*rectangle.before.get_pixel(2, 2), Rgb([255, 255, 255]));
+        assert_eq!(*rectangle.after.get_pixel(2, 2), Rgb([255, 69, 0]));
+        assert_eq!(*rectangle.after.get_pixel(1, 1), Rgb([255, 255, 255]));
+        assert_eq!(
+            parser.users().hash(rectangle.user),
+            "qJ7O6cuUNfkDyn+ZOEYR+UiVEmAu/vYfm/s4hK0XJytqAxyZqXvq14/picpitWbtaq8gyuluh+K4Uby1aquGRA=="
+        );
+
+        let circle = &events[1];
+        assert_eq!(circle.affected_pixels(), 317);
+        assert_eq!(circle.before.dimensions(), (25, 25));
+
+        remove_dir_all(&output_dir).unwrap();
+    }
+}
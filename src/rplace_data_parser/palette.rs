@@ -0,0 +1,180 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use image::Rgb;
+
+use crate::config_file::hex_color;
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Full(Rgb<u8>),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaletteError::Full(color) => write!(
+                f,
+                "No room left in the palette for color {}, at most {} colors can be stored",
+                hex_color::to_hex(color),
+                MAX_COLORS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+/// Colors a canvas stores as one byte per pixel. Starts out with the official palette of 2023,
+/// colors outside of it are added while there is room and reported as unknown.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    colors: Vec<Rgb<u8>>,
+    /// Index of every color, keyed by its packed RGB value.
+    indices: HashMap<u32, u8>,
+    /// Records that used each color added after the official ones.
+    unknown_counts: Vec<u32>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::official_2023()
+    }
+}
+
+impl Palette {
+    pub fn official_2023() -> Palette {
+        Palette {
+            colors: OFFICIAL_2023_COLORS.to_vec(),
+            indices: OFFICIAL_2023_COLORS
+                .iter()
+                .enumerate()
+                .map(|(index, &color)| (pack(color), index as u8))
+                .collect(),
+            unknown_counts: vec![],
+        }
+    }
+
+    pub fn colors(&self) -> &[Rgb<u8>] {
+        &self.colors
+    }
+
+    pub fn color(&self, index: u8) -> Rgb<u8> {
+        self.colors[index as usize]
+    }
+
+    pub fn find(&self, color: Rgb<u8>) -> Option<u8> {
+        self.indices.get(&pack(color)).copied()
+    }
+
+    /// Index of the color, adding it when it isn't in the palette yet. Fails once all 256
+    /// indices are taken, as any other index would stand for a different color.
+    pub fn index_of(&mut self, color: Rgb<u8>) -> Result<u8> {
+        let index = match self.find(color) {
+            Some(index) => index,
+            None if self.colors.len() < MAX_COLORS => {
+                let index = self.colors.len() as u8;
+
+                self.colors.push(color);
+                self.indices.insert(pack(color), index);
+                self.unknown_counts.push(0);
+
+                index
+            }
+            None => return Err(anyhow!(PaletteError::Full(color))),
+        };
+
+        if let Some(count) = (index as usize)
+            .checked_sub(OFFICIAL_2023_COLORS.len())
+            .and_then(|unknown_index| self.unknown_counts.get_mut(unknown_index))
+        {
+            *count += 1;
+        }
+
+        Ok(index)
+    }
+
+    /// Colors outside of the official palette with the number of records that used them, in the
+    /// order they were first seen.
+    pub fn unknown_colors(&self) -> Vec<(Rgb<u8>, u32)> {
+        self.colors[OFFICIAL_2023_COLORS.len()..]
+            .iter()
+            .copied()
+            .zip(self.unknown_counts.iter().copied())
+            .collect()
+    }
+}
+
+fn pack(Rgb([r, g, b]): Rgb<u8>) -> u32 {
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+const MAX_COLORS: usize = 256;
+
+const OFFICIAL_2023_COLORS: [Rgb<u8>; 32] = [
+    Rgb([0x6D, 0x00, 0x1A]),
+    Rgb([0xBE, 0x00, 0x39]),
+    Rgb([0xFF, 0x45, 0x00]),
+    Rgb([0xFF, 0xA8, 0x00]),
+    Rgb([0xFF, 0xD6, 0x35]),
+    Rgb([0xFF, 0xF8, 0xB8]),
+    Rgb([0x00, 0xA3, 0x68]),
+    Rgb([0x00, 0xCC, 0x78]),
+    Rgb([0x7E, 0xED, 0x56]),
+    Rgb([0x00, 0x75, 0x6F]),
+    Rgb([0x00, 0x9E, 0xAA]),
+    Rgb([0x00, 0xCC, 0xC0]),
+    Rgb([0x24, 0x50, 0xA4]),
+    Rgb([0x36, 0x90, 0xEA]),
+    Rgb([0x51, 0xE9, 0xF4]),
+    Rgb([0x49, 0x3A, 0xC1]),
+    Rgb([0x6A, 0x5C, 0xFF]),
+    Rgb([0x94, 0xB3, 0xFF]),
+    Rgb([0x81, 0x1E, 0x9F]),
+    Rgb([0xB4, 0x4A, 0xC0]),
+    Rgb([0xE4, 0xAB, 0xFF]),
+    Rgb([0xDE, 0x10, 0x7F]),
+    Rgb([0xFF, 0x38, 0x81]),
+    Rgb([0xFF, 0x99, 0xAA]),
+    Rgb([0x6D, 0x48, 0x2F]),
+    Rgb([0x9C, 0x69, 0x26]),
+    Rgb([0xFF, 0xB4, 0x70]),
+    Rgb([0x00, 0x00, 0x00]),
+    Rgb([0x51, 0x52, 0x52]),
+    Rgb([0x89, 0x8D, 0x90]),
+    Rgb([0xD4, 0xD7, 0xD9]),
+    Rgb([0xFF, 0xFF, 0xFF]),
+];
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::Palette;
+
+    #[test]
+    fn test_palette() {
+        let mut palette = Palette::official_2023();
+        let unknown = Rgb([1, 2, 3]);
+
+        assert_eq!(palette.index_of(Rgb([255, 69, 0])).unwrap(), 2);
+        assert_eq!(palette.index_of(unknown).unwrap(), 32);
+        assert_eq!(palette.index_of(unknown).unwrap(), 32);
+        assert_eq!(palette.color(32), unknown);
+        assert_eq!(palette.unknown_colors(), vec![(unknown, 2)]);
+
+        for value in 0..=222 {
+            palette.index_of(Rgb([value, value, 100])).unwrap();
+        }
+
+        assert_eq!(palette.colors().len(), 256);
+        assert_eq!(palette.find(Rgb([222, 222, 100])), Some(255));
+        // No room left, known colors still work
+        assert!(palette.index_of(Rgb([0, 0, 1])).is_err());
+        assert_eq!(palette.index_of(Rgb([0, 0, 0])).unwrap(), 27);
+        assert_eq!(palette.index_of(unknown).unwrap(), 32);
+        assert_eq!(palette.unknown_colors().len(), 224);
+        assert_eq!(palette.unknown_colors()[0], (unknown, 3));
+    }
+}
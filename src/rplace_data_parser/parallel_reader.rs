@@ -0,0 +1,310 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::File,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use csv::{ByteRecord, ReaderBuilder};
+use memmap2::Mmap;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{
+    config::OnError,
+    record::Record,
+    record_filter::{RecordFilter, UserDecisions},
+    user_interner::{UserId, UserInterner},
+};
+
+/// Accepted records of a piece sorted by timestamp, with the users interned into a table of
+/// their own so that pieces can be read without sharing one.
+pub(super) struct Piece {
+    records: Vec<Record>,
+    users: UserInterner,
+}
+
+impl Piece {
+    /// Moves the users of the records into the shared table, in the order they first appear.
+    pub(super) fn into_records(self, users: &mut UserInterner) -> Vec<Record> {
+        let Piece {
+            mut records,
+            users: piece_users,
+        } = self;
+        let mut ids: Vec<Option<UserId>> = vec![None; piece_users.len()];
+
+        for record in &mut records {
+            record.user = *ids[record.user.0 as usize]
+                .get_or_insert_with(|| users.intern(piece_users.hash(record.user)));
+        }
+
+        records
+    }
+}
+
+/// Pieces of a batch in file order. With `OnError::Stop` reading stops at the first invalid
+/// record, the pieces then only hold the records before it and the error is kept.
+pub(super) struct Batch {
+    pub(super) pieces: Vec<Piece>,
+    pub(super) error: Option<anyhow::Error>,
+}
+
+/// Reads the files one after the other, each split into pieces of about `piece_bytes` at line
+/// boundaries that are read in parallel. Only one file is mapped at a time, and files after an
+/// invalid record aren't read with `OnError::Stop`.
+pub(super) fn read_batch(
+    paths: &[PathBuf],
+    filters: &[&dyn RecordFilter],
+    on_error: OnError,
+    piece_bytes: usize,
+) -> Result<Batch> {
+    let mut batch = Batch {
+        pieces: vec![],
+        error: None,
+    };
+
+    for path in paths {
+        let file = File::open(path)?;
+
+        // SAFETY: the file is only read and is expected not to be modified while it is mapped
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let results: Vec<(Piece, Option<anyhow::Error>)> = split_into_pieces(&mmap, piece_bytes)
+            .into_par_iter()
+            .map(|range| read_piece(&mmap[range], path, filters, on_error))
+            .collect();
+
+        for (piece, error) in results {
+            batch.pieces.push(piece);
+
+            if error.is_some() {
+                batch.error = error;
+                return Ok(batch);
+            }
+        }
+    }
+
+    Ok(batch)
+}
+
+/// Reads the records of a piece, up to the first invalid one with `OnError::Stop`.
+fn read_piece(
+    bytes: &[u8],
+    path: &Path,
+    filters: &[&dyn RecordFilter],
+    on_error: OnError,
+) -> (Piece, Option<anyhow::Error>) {
+    let mut reader = ReaderBuilder::new().has_headers(false).from_reader(bytes);
+    let mut byte_record = ByteRecord::new();
+    let mut users = UserInterner::new();
+    let mut decisions: Vec<UserDecisions> = filters.iter().map(|_| UserDecisions::new()).collect();
+    let mut records = vec![];
+    let mut error = None;
+
+    loop {
+        let result = match reader.read_byte_record(&mut byte_record) {
+            Ok(false) => break,
+            Ok(true) => {
+                Record::from_byte_record(&byte_record, &mut users).map_err(|err| anyhow!(err))
+            }
+            Err(err) => Err(anyhow!(err)),
+        };
+
+        let record = match result {
+            Ok(record) => record,
+            Err(err) => match on_error {
+                OnError::Nothing => continue,
+                OnError::Print => {
+                    eprintln!(
+                        "Error parsing record in {}: {}. Skipping",
+                        path.display(),
+                        err
+                    );
+                    continue;
+                }
+                OnError::Stop => {
+                    error =
+                        Some(err.context(format!("Error parsing record in {}", path.display())));
+                    break;
+                }
+            },
+        };
+
+        let accepted = filters
+            .iter()
+            .zip(&mut decisions)
+            .all(|(filter, decisions)| filter.accepts(&record, &users, decisions));
+
+        if accepted {
+            records.push(record);
+        }
+    }
+
+    records.sort_by_key(|record| record.timestamp);
+
+    (Piece { records, users }, error)
+}
+
+/// Ranges of whole lines after the header line, each at least `piece_bytes` long except the
+/// last one.
+fn split_into_pieces(bytes: &[u8], piece_bytes: usize) -> Vec<Range<usize>> {
+    let line_end = |from: usize| {
+        bytes[from..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(bytes.len(), |position| from + position + 1)
+    };
+
+    let mut pieces = vec![];
+    let mut start = line_end(0);
+
+    while start < bytes.len() {
+        let end = line_end((start + piece_bytes.max(1)).min(bytes.len()) - 1);
+
+        pieces.push(start..end);
+        start = end;
+    }
+
+    pieces
+}
+
+/// Merges pieces sorted by timestamp into one sequence sorted by timestamp. Records with the
+/// same timestamp keep the order of their pieces.
+pub(super) struct TimestampMerge {
+    pieces: Vec<std::vec::IntoIter<Record>>,
+    heads: Vec<Option<Record>>,
+    queue: BinaryHeap<Reverse<(NaiveDateTime, usize)>>,
+}
+
+impl TimestampMerge {
+    pub(super) fn new(pieces: Vec<Vec<Record>>) -> TimestampMerge {
+        let mut pieces: Vec<std::vec::IntoIter<Record>> =
+            pieces.into_iter().map(|piece| piece.into_iter()).collect();
+        let mut queue = BinaryHeap::new();
+
+        let heads = pieces
+            .iter_mut()
+            .enumerate()
+            .map(|(index, piece)| {
+                let head = piece.next();
+                if let Some(record) = &head {
+                    queue.push(Reverse((record.timestamp, index)));
+                }
+                head
+            })
+            .collect();
+
+        TimestampMerge {
+            pieces,
+            heads,
+            queue,
+        }
+    }
+}
+
+impl Iterator for TimestampMerge {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        let Reverse((_, index)) = self.queue.pop()?;
+        let next = self.pieces[index].next();
+
+        if let Some(record) = &next {
+            self.queue.push(Reverse((record.timestamp, index)));
+        }
+
+        std::mem::replace(&mut self.heads[index], next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{remove_file, write},
+        path::PathBuf,
+    };
+
+    use csv::Reader;
+
+    use crate::rplace_data_parser::{OnError, RawRecord, Record, UserInterner};
+
+    use super::{read_batch, Batch, TimestampMerge};
+
+    #[test]
+    fn test_parallel_read_matches_sequential_read() {
+        let paths: Vec<PathBuf> = (0..=5)
+            .rev()
+            .map(|chunk| {
+                PathBuf::from(format!(
+                    "assets/rplace_data_sample/2023_place_canvas_history-{:012}.csv",
+                    chunk
+                ))
+            })
+            .collect();
+
+        let mut expected: Vec<RawRecord> = vec![];
+        for path in &paths {
+            let mut reader = Reader::from_path(path).unwrap();
+            expected.extend(reader.deserialize().map(|result| result.unwrap()));
+        }
+        expected.sort_by_key(|record| record.timestamp);
+
+        let batch = read_batch(&paths, &[], OnError::Stop, 1000).unwrap();
+        assert!(batch.error.is_none());
+        assert!(batch.pieces.len() > paths.len());
+
+        let mut users = UserInterner::new();
+        let pieces = batch
+            .pieces
+            .into_iter()
+            .map(|piece| piece.into_records(&mut users))
+            .collect();
+        let merged: Vec<Record> = TimestampMerge::new(pieces).collect();
+
+        assert_eq!(merged.len(), expected.len());
+        for (merged, expected) in merged.iter().zip(&expected) {
+            assert_eq!(merged.timestamp, expected.timestamp);
+            assert_eq!(users.hash(merged.user), expected.user);
+            assert_eq!(merged.coordinate, expected.coordinate);
+        }
+        assert!(users.len() < merged.len());
+
+        let invalid = std::env::temp_dir().join(format!(
+            "pixel_crab_test_invalid_records_{}.csv",
+            std::process::id()
+        ));
+        write(
+            &invalid,
+            "timestamp,user,coordinate,pixel_color\n\
+             2023-07-20 13:00:26.088 UTC,a,\"1,2\",#FFFFFF\n\
+             2023-07-20 13:00:27 UTC,b,\"1\",#FFFFFF\n\
+             2023-07-20 13:00:28 UTC,c,\"1,2\",#FFFFFF\n",
+        )
+        .unwrap();
+
+        let invalid = [invalid];
+        let record_count = |batch: &Batch| {
+            batch
+                .pieces
+                .iter()
+                .map(|piece| piece.records.len())
+                .sum::<usize>()
+        };
+
+        // Only the records before the invalid one are kept when stopping
+        for piece_bytes in [8, 1000] {
+            let batch = read_batch(&invalid, &[], OnError::Stop, piece_bytes).unwrap();
+
+            assert!(batch.error.is_some());
+            assert_eq!(record_count(&batch), 1);
+        }
+
+        let batch = read_batch(&invalid, &[], OnError::Nothing, 8).unwrap();
+        assert!(batch.error.is_none());
+        assert_eq!(record_count(&batch), 2);
+
+        remove_file(&invalid[0]).unwrap();
+    }
+}
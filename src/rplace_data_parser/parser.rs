@@ -1,18 +1,29 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+};
 
 use anyhow::Result;
 use chrono::NaiveDateTime;
-use csv::Reader;
+
+use crate::config_file::{hex_color, ConfigFile};
 
 use super::{
-    config::{OnError, ParserConfig},
+    config::ParserConfig,
+    parallel_reader::{read_batch, Batch, TimestampMerge},
     parser_image::ParserImage,
     record::Record,
+    record_filter::RecordFilter,
+    replay_observer::ReplayObserver,
+    user_interner::UserInterner,
 };
 
 pub struct Parser {
     config: ParserConfig,
     parser_image: ParserImage,
+    filters: Vec<Box<dyn RecordFilter>>,
+    users: UserInterner,
 }
 
 impl Parser {
@@ -20,53 +31,300 @@ impl Parser {
         Parser {
             config,
             parser_image: ParserImage::new(),
+            filters: vec![],
+            users: UserInterner::new(),
         }
     }
 
+    /// Every user of the records replayed so far, the ids stay the same across calls to `parse`.
+    pub fn users(&self) -> &UserInterner {
+        &self.users
+    }
+
+    /// Adds a filter on top of the ones of the config.
+    pub fn add_filter(&mut self, filter: Box<dyn RecordFilter>) {
+        self.filters.push(filter);
+    }
+
     pub fn parse(&mut self, paths: &[PathBuf]) -> Result<()> {
-        let mut first_timestamp: Option<NaiveDateTime> = None;
-        let mut last_action: u32 = 0;
-
-        for path in paths {
-            let mut reader = Reader::from_path(path)?;
-
-            for result in reader.deserialize() {
-                let record: Record = match result {
-                    Ok(record) => record,
-                    Err(err) => match self.config.on_error {
-                        OnError::Nothing => continue,
-                        OnError::Print => {
-                            eprintln!("Error parsing record: {}. Skipping", err);
-                            continue;
-                        }
-                        OnError::Stop => return Err(err.into()),
-                    },
-                };
+        self.parse_with(paths, &mut [])
+    }
+
+    /// Replays the records like `parse`, showing every record to the observers before and after
+    /// it is drawn.
+    ///
+    /// Batches of files are parsed in parallel on a background thread while the previous batch is
+    /// replayed. The records of a batch are merged by timestamp, and the records of a batch that
+    /// are newer than the first record of the next batch are merged into that one, so files may
+    /// overlap their neighbours. Records that are still older than one replayed before them, when
+    /// files are out of order, are replayed anyway and reported at the end. With `OnError::Stop`
+    /// the records read before the invalid one are replayed before the error is returned.
+    ///
+    /// Users are interned while parsing and the table is saved as `users.csv` into the output
+    /// directory at the end. Colors outside of the official palette are reported at the end as
+    /// well, replaying fails once there are more of them than the palette can store.
+    pub fn parse_with(
+        &mut self,
+        paths: &[PathBuf],
+        observers: &mut [&mut dyn ReplayObserver],
+    ) -> Result<()> {
+        self.config.validate()?;
 
-                self.parser_image.handle_record(&record);
+        let config_filters = self.config.filter.build()?;
 
-                match first_timestamp {
-                    None => {
-                        first_timestamp = Some(record.timestamp);
+        ConfigFile::save_effective(&self.config, &self.config.output_dir)?;
+
+        let Parser {
+            config,
+            parser_image,
+            filters,
+            users,
+        } = self;
+
+        let filters: Vec<&dyn RecordFilter> = config_filters
+            .iter()
+            .chain(filters.iter())
+            .map(|filter| filter.as_ref())
+            .collect();
+
+        let mut clock = ReplayClock::default();
+
+        thread::scope(|scope| -> Result<()> {
+            let (sender, receiver) = mpsc::sync_channel(1);
+            let filters = &filters;
+            let on_error = config.on_error;
+            let batch_files = config.batch_files as usize;
+
+            scope.spawn(move || {
+                for batch in paths.chunks(batch_files) {
+                    let batch = read_batch(batch, filters, on_error, PIECE_BYTES);
+                    let failed = batch.as_ref().map_or(true, |batch| batch.error.is_some());
+
+                    if sender.send(batch).is_err() || failed {
+                        break;
                     }
-                    Some(first_timestamp) => {
-                        let elapsed_time = record.timestamp - first_timestamp;
-                        let elapsed_seconds = elapsed_time.num_seconds() as u32;
+                }
+            });
+
+            let mut replay = |record: &Record, users: &UserInterner| -> Result<()> {
+                for observer in observers.iter_mut() {
+                    observer.before_record(record, parser_image, users);
+                }
 
-                        if elapsed_seconds >= (last_action + self.config.save_interval_seconds) {
-                            let elapsed_intervals =
-                                (elapsed_seconds - last_action) / self.config.save_interval_seconds;
+                parser_image.handle_record(record)?;
 
-                            last_action += elapsed_intervals * self.config.save_interval_seconds;
+                for observer in observers.iter_mut() {
+                    observer.after_record(record, parser_image, users);
+                }
 
-                            self.parser_image
-                                .save_image(&self.config.output_dir, elapsed_seconds);
-                        }
+                if let Some(elapsed_seconds) =
+                    clock.advance(record.timestamp, config.save_interval_seconds)
+                {
+                    parser_image.save_image(&config.output_dir, elapsed_seconds);
+                }
+
+                Ok(())
+            };
+
+            // Merged records of the last batch, replayed as far as the next batch shows that it
+            // doesn't overlap them
+            let mut pending = TimestampMerge::new(vec![]).peekable();
+            let mut outcome = Ok(());
+
+            for batch in receiver {
+                let Batch { pieces, error } = match batch {
+                    Ok(batch) => batch,
+                    Err(err) => {
+                        outcome = Err(err);
+                        break;
                     }
                 };
+
+                let mut pieces: Vec<Vec<Record>> = pieces
+                    .into_iter()
+                    .map(|piece| piece.into_records(users))
+                    .collect();
+                let batch_start = pieces
+                    .iter()
+                    .filter_map(|piece| piece.first())
+                    .map(|record| record.timestamp)
+                    .min();
+
+                if let Some(batch_start) = batch_start {
+                    while let Some(record) =
+                        pending.next_if(|record| record.timestamp <= batch_start)
+                    {
+                        replay(&record, users)?;
+                    }
+
+                    pieces.insert(0, pending.collect());
+                    pending = TimestampMerge::new(pieces).peekable();
+                }
+
+                if let Some(err) = error {
+                    outcome = Err(err);
+                    break;
+                }
             }
+
+            for record in pending {
+                replay(&record, users)?;
+            }
+
+            outcome
+        })?;
+
+        if clock.out_of_order > 0 {
+            eprintln!(
+                "{} record(s) were older than records replayed before them, files are expected in \
+                 chronological order",
+                clock.out_of_order
+            );
+        }
+
+        for observer in observers.iter_mut() {
+            observer.finish(parser_image, users);
+        }
+
+        users.save(&Path::new(&config.output_dir).join("users.csv"))?;
+
+        for (color, records) in parser_image.palette().unknown_colors() {
+            eprintln!(
+                "Color {} is not in the official palette, used by {} record(s)",
+                hex_color::to_hex(&color),
+                records
+            );
         }
 
         Ok(())
     }
 }
+
+/// Time replayed so far, to save the canvas every save interval.
+#[derive(Default)]
+struct ReplayClock {
+    first_timestamp: Option<NaiveDateTime>,
+    latest_timestamp: Option<NaiveDateTime>,
+    last_action: u32,
+    out_of_order: u64,
+}
+
+impl ReplayClock {
+    /// Returns the seconds passed since the first record when the canvas is due to be saved.
+    /// Records older than the latest one are only counted.
+    fn advance(&mut self, timestamp: NaiveDateTime, save_interval_seconds: u32) -> Option<u32> {
+        if self
+            .latest_timestamp
+            .is_some_and(|latest| timestamp < latest)
+        {
+            self.out_of_order += 1;
+            return None;
+        }
+
+        self.latest_timestamp = Some(timestamp);
+
+        let first_timestamp = *self.first_timestamp.get_or_insert(timestamp);
+        let elapsed_seconds = (timestamp - first_timestamp).num_seconds() as u32;
+
+        if elapsed_seconds < self.last_action + save_interval_seconds {
+            return None;
+        }
+
+        let elapsed_intervals = (elapsed_seconds - self.last_action) / save_interval_seconds;
+        self.last_action += elapsed_intervals * save_interval_seconds;
+
+        Some(elapsed_seconds)
+    }
+}
+
+const PIECE_BYTES: usize = 16 * 1024 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use chrono::NaiveDateTime;
+
+    use crate::rplace_data_parser::{
+        OnError, ParserConfig, ParserImage, Record, ReplayObserver, UserInterner,
+    };
+
+    use super::Parser;
+
+    struct Timestamps(Vec<NaiveDateTime>);
+
+    impl ReplayObserver for Timestamps {
+        fn after_record(&mut self, record: &Record, _canvas: &ParserImage, _users: &UserInterner) {
+            self.0.push(record.timestamp);
+        }
+    }
+
+    #[test]
+    fn test_replay_overlapping_batches_in_order() {
+        let directory =
+            std::env::temp_dir().join(format!("pixel_crab_test_parser_{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+
+        let files = [
+            ["13:00:00", "13:00:10", "13:00:20"],
+            ["13:00:15", "13:00:25", "13:00:30"],
+            ["13:00:30", "13:00:40", "13:00:05"],
+        ];
+        let paths: Vec<_> = files
+            .iter()
+            .enumerate()
+            .map(|(index, times)| {
+                let path = directory.join(format!("{}.csv", index));
+                let mut content = String::from("timestamp,user,coordinate,pixel_color\n");
+
+                for time in times {
+                    let coordinate = if *time == "13:00:25" { "1" } else { "1,2" };
+                    content.push_str(&format!(
+                        "2023-07-20 {} UTC,u,\"{}\",#FFFFFF\n",
+                        time, coordinate
+                    ));
+                }
+
+                write(&path, content).unwrap();
+                path
+            })
+            .collect();
+
+        let replayed = |on_error: OnError| {
+            let mut timestamps = Timestamps(vec![]);
+            let mut parser = Parser::new(ParserConfig {
+                output_dir: directory.join("output").to_string_lossy().to_string(),
+                on_error,
+                batch_files: 1,
+                ..ParserConfig::new_default()
+            });
+
+            let result = parser.parse_with(&paths, &mut [&mut timestamps]);
+            let times: Vec<String> = timestamps
+                .0
+                .iter()
+                .map(|timestamp| timestamp.format("%H:%M:%S").to_string())
+                .collect();
+
+            (result.is_ok(), times.join(" "))
+        };
+
+        // Every batch overlaps the one before, only 13:00:05 comes after records replayed before
+        assert_eq!(
+            replayed(OnError::Nothing),
+            (
+                true,
+                String::from(
+                    "13:00:00 13:00:10 13:00:05 13:00:15 13:00:20 13:00:30 13:00:30 13:00:40"
+                )
+            )
+        );
+        // The records before the invalid one are still replayed
+        assert_eq!(
+            replayed(OnError::Stop),
+            (false, String::from("13:00:00 13:00:10 13:00:15 13:00:20"))
+        );
+
+        remove_dir_all(&directory).unwrap();
+    }
+}
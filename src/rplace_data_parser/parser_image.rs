@@ -1,38 +1,130 @@
-use image::{imageops, ImageBuffer, Rgb, RgbImage};
+use anyhow::Result;
+use image::{imageops, GrayImage, ImageBuffer, Luma, Rgb, RgbImage};
 
 use crate::image_io::ImageIO;
 
-use super::record::{Coordinate, Record};
+use super::{
+    palette::Palette,
+    record::{Coordinate, Record},
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ImageExpansionOffset {
     left: i32,
     top: i32,
 }
 
+/// Canvas storing the palette index of every pixel instead of its color.
+#[derive(Clone)]
 pub struct ParserImage {
-    image: RgbImage,
+    image: GrayImage,
+    palette: Palette,
+    background: Luma<u8>,
     image_expansion_offset: ImageExpansionOffset,
 }
 
+impl Default for ParserImage {
+    fn default() -> Self {
+        ParserImage::new()
+    }
+}
+
 impl ParserImage {
     pub fn new() -> ParserImage {
+        let palette = Palette::official_2023();
+        let background = Luma([palette
+            .find(BACKGROUND_COLOR)
+            .expect("The background is one of the official colors")]);
+
         ParserImage {
-            image: RgbImage::new(0, 0),
+            image: GrayImage::new(0, 0),
+            palette,
+            background,
             image_expansion_offset: ImageExpansionOffset { left: 0, top: 0 },
         }
     }
 
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    /// Palette index of every pixel, the top left pixel belongs to `origin`.
+    pub fn indices(&self) -> &GrayImage {
+        &self.image
+    }
+
+    /// Colors behind the indices, including the unknown colors seen so far.
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn to_rgb_image(&self) -> RgbImage {
+        RgbImage::from_fn(self.image.width(), self.image.height(), |x, y| {
+            self.palette.color(self.image.get_pixel(x, y).0[0])
+        })
+    }
+
+    /// Coordinates of the dataset the top left pixel of the image belongs to.
+    pub fn origin(&self) -> (i32, i32) {
+        let ImageExpansionOffset { left, top } = self.image_expansion_offset;
+
+        (-left, -top)
+    }
+
+    /// Color at coordinates of the dataset, pixels the canvas hasn't expanded to yet are white.
+    pub fn get_pixel(&self, (x, y): (i32, i32)) -> Rgb<u8> {
+        let ImageExpansionOffset { left, top } = self.image_expansion_offset;
+        let (img_width, img_height) = self.image.dimensions();
+        let (x_with_offset, y_with_offset) = (x + left, y + top);
+
+        if x_with_offset < 0
+            || y_with_offset < 0
+            || x_with_offset >= img_width as i32
+            || y_with_offset >= img_height as i32
+        {
+            return BACKGROUND_COLOR;
+        }
+
+        let Luma([index]) = *self
+            .image
+            .get_pixel(x_with_offset as u32, y_with_offset as u32);
+
+        self.palette.color(index)
+    }
+
+    /// Copies the area between the two corners given in coordinates of the dataset, both
+    /// inclusive.
+    pub fn crop(&self, (min_x, min_y): (i32, i32), (max_x, max_y): (i32, i32)) -> RgbImage {
+        let width = (max_x - min_x + 1).max(0) as u32;
+        let height = (max_y - min_y + 1).max(0) as u32;
+
+        RgbImage::from_fn(width, height, |x, y| {
+            self.get_pixel((min_x + x as i32, min_y + y as i32))
+        })
+    }
+
+    /// Writes the canvas as an indexed PNG.
     pub fn save_image(&self, output_dir: &str, seconds_passed: u32) {
-        ImageIO::save_image(&self.image, output_dir, &seconds_passed.to_string(), ".png").unwrap()
+        ImageIO::save_indexed_image(
+            &self.image,
+            self.palette.colors(),
+            output_dir,
+            &seconds_passed.to_string(),
+        )
+        .unwrap()
     }
 
-    pub fn handle_record(&mut self, record: &Record) {
+    /// Fails without drawing anything when the color of the record doesn't fit into the palette.
+    pub fn handle_record(&mut self, record: &Record) -> Result<()> {
+        let index = Luma([self.palette.index_of(record.pixel_color)?]);
+
         self.handle_image_expansion(&record.coordinate);
-        self.draw_from_record(record);
+        self.draw_from_record(record, index);
+
+        Ok(())
     }
 
-    fn draw_from_record(&mut self, record: &Record) {
+    fn draw_from_record(&mut self, record: &Record, index: Luma<u8>) {
         let ImageExpansionOffset { left, top } = self.image_expansion_offset;
         let offset_left = left;
         let offset_top = top;
@@ -42,11 +134,8 @@ impl ParserImage {
                 let x_with_offset = x + offset_left;
                 let y_with_offset = y + offset_top;
 
-                self.image.put_pixel(
-                    x_with_offset as u32,
-                    y_with_offset as u32,
-                    record.pixel_color,
-                );
+                self.image
+                    .put_pixel(x_with_offset as u32, y_with_offset as u32, index);
             }
             Coordinate::Rectangle { x1, y1, x2, y2 } => {
                 let x1_with_offset = x1 + offset_left;
@@ -56,7 +145,7 @@ impl ParserImage {
 
                 for y in y1_with_offset..=(y2_with_offset) {
                     for x in x1_with_offset..=(x2_with_offset) {
-                        self.image.put_pixel(x as u32, y as u32, record.pixel_color);
+                        self.image.put_pixel(x as u32, y as u32, index);
                     }
                 }
             }
@@ -65,12 +154,12 @@ impl ParserImage {
                 let y_with_offset = y + offset_top;
                 let r = r as i32;
 
-                for y in (x_with_offset - r)..=(x_with_offset + r) {
-                    for x in (y_with_offset - r)..=(y_with_offset + r) {
+                for y in (y_with_offset - r)..=(y_with_offset + r) {
+                    for x in (x_with_offset - r)..=(x_with_offset + r) {
                         let dx = x - x_with_offset;
                         let dy = y - y_with_offset;
                         if dx * dx + dy * dy <= r * r {
-                            self.image.put_pixel(x as u32, y as u32, record.pixel_color);
+                            self.image.put_pixel(x as u32, y as u32, index);
                         }
                     }
                 }
@@ -152,9 +241,9 @@ impl ParserImage {
             let new_width = img_width + expand_left + expand_right;
 
             let mut new_image =
-                ImageBuffer::from_pixel(new_width as u32, new_height as u32, Rgb([255, 255, 255]));
+                ImageBuffer::from_pixel(new_width as u32, new_height as u32, self.background);
 
-            imageops::overlay(
+            imageops::replace(
                 &mut new_image,
                 &self.image,
                 expand_left.into(),
@@ -167,3 +256,144 @@ impl ParserImage {
         }
     }
 }
+
+const BACKGROUND_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{remove_dir_all, File},
+        path::PathBuf,
+    };
+
+    use chrono::NaiveDateTime;
+    use csv::Reader;
+    use image::Rgb;
+
+    use crate::{
+        image_io::ImageIO,
+        rplace_data_parser::{Coordinate, RawRecord, Record, UserId, UserInterner},
+    };
+
+    use super::ParserImage;
+
+    #[test]
+    fn test_draw_records_matches_golden_images() {
+        for name in ["different_forms_of_coordinates", "off_diagonal_circles"] {
+            let mut parser_image = ParserImage::new();
+            let mut users = UserInterner::new();
+            let mut reader =
+                Reader::from_path(format!("assets/rplace_data_sample/{}.csv", name)).unwrap();
+
+            for result in reader.deserialize() {
+                let record: RawRecord = result.unwrap();
+                parser_image
+                    .handle_record(&record.intern(&mut users))
+                    .unwrap();
+            }
+
+            let golden =
+                ImageIO::load_rgb_image(&PathBuf::from(format!("assets/golden/{}.png", name)))
+                    .unwrap();
+
+            assert_eq!(parser_image.dimensions(), golden.dimensions());
+            assert!(
+                parser_image.to_rgb_image() == golden,
+                "{} differs from golden",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_draw_records_at_their_coordinates() {
+        let mut parser_image = ParserImage::new();
+        let mut users = UserInterner::new();
+        let mut reader = Reader::from_path("assets/rplace_data_sample/visible_shapes.csv").unwrap();
+
+        for result in reader.deserialize() {
+            let record: RawRecord = result.unwrap();
+            parser_image
+                .handle_record(&record.intern(&mut users))
+                .unwrap();
+        }
+
+        let white = Rgb([255, 255, 255]);
+        let blue = Rgb([54, 144, 234]);
+        let green = Rgb([0, 163, 104]);
+        let orange = Rgb([255, 69, 0]);
+        let black = Rgb([0, 0, 0]);
+
+        // The canvas spans from the point at x = -4 to the right edge of the circle at x = 33, and
+        // from the origin to the bottom edge of the circle at y = 23.
+        assert_eq!(parser_image.origin(), (-4, 0));
+        assert_eq!(parser_image.dimensions(), (38, 24));
+
+        assert_eq!(parser_image.get_pixel((-4, 7)), blue);
+        assert_eq!(parser_image.get_pixel((-3, 7)), white);
+        assert_eq!(parser_image.get_pixel((-4, 6)), white);
+
+        for corner in [(2, 3), (5, 3), (2, 6), (5, 6)] {
+            assert_eq!(parser_image.get_pixel(corner), green, "{:?}", corner);
+        }
+        for outside in [(1, 3), (6, 3), (2, 2), (5, 7)] {
+            assert_eq!(parser_image.get_pixel(outside), white, "{:?}", outside);
+        }
+
+        // Pixels at most 3 away from (30, 20): 3^2 + 0^2 and 2^2 + 2^2 are inside, 3^2 + 1^2 and
+        // 2^2 + 3^2 are not.
+        for inside in [(27, 20), (33, 20), (30, 17), (30, 23), (28, 18), (32, 22)] {
+            assert_eq!(parser_image.get_pixel(inside), orange, "{:?}", inside);
+        }
+        for outside in [(26, 20), (34, 20), (30, 16), (27, 19), (33, 21), (28, 17)] {
+            assert_eq!(parser_image.get_pixel(outside), white, "{:?}", outside);
+        }
+
+        // The later point is drawn over the center of the circle.
+        assert_eq!(parser_image.get_pixel((30, 20)), black);
+    }
+
+    #[test]
+    fn test_save_indexed_snapshot() {
+        let mut parser_image = ParserImage::new();
+        let mut users = UserInterner::new();
+        let mut reader =
+            Reader::from_path("assets/rplace_data_sample/off_diagonal_circles.csv").unwrap();
+
+        for result in reader.deserialize() {
+            let record: RawRecord = result.unwrap();
+            parser_image
+                .handle_record(&record.intern(&mut users))
+                .unwrap();
+        }
+
+        let unknown = Rgb([1, 2, 3]);
+        parser_image
+            .handle_record(&Record {
+                timestamp: NaiveDateTime::default(),
+                user: UserId(0),
+                coordinate: Coordinate::Point { x: 0, y: 0 },
+                pixel_color: unknown,
+            })
+            .unwrap();
+
+        assert_eq!(parser_image.get_pixel((0, 0)), unknown);
+        assert_eq!(parser_image.palette().unknown_colors(), vec![(unknown, 1)]);
+
+        let output_dir = std::env::temp_dir().join(format!(
+            "pixel_crab_test_indexed_snapshot_{}",
+            std::process::id()
+        ));
+        let output_dir = output_dir.to_string_lossy();
+        parser_image.save_image(&output_dir, 0);
+
+        let path = PathBuf::from(format!("{}/0.png", output_dir));
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+
+        assert_eq!(reader.info().color_type, png::ColorType::Indexed);
+        assert!(ImageIO::load_rgb_image(&path).unwrap() == parser_image.to_rgb_image());
+
+        remove_dir_all(output_dir.as_ref()).unwrap();
+    }
+}
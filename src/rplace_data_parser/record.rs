@@ -1,26 +1,143 @@
+use core::fmt;
+use std::borrow::Cow;
+
 use anyhow::Result;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Timelike};
 use image::Rgb;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::config_file::hex_color;
+
+use super::user_interner::{UserId, UserInterner};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Coordinate {
     Point { x: i32, y: i32 },
     Rectangle { x1: i32, y1: i32, x2: i32, y2: i32 },
     Circle { x: i32, y: i32, r: u32 },
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shape {
+    Point,
+    Rectangle,
+    Circle,
+}
+
+impl Coordinate {
+    pub fn shape(&self) -> Shape {
+        match self {
+            Coordinate::Point { .. } => Shape::Point,
+            Coordinate::Rectangle { .. } => Shape::Rectangle,
+            Coordinate::Circle { .. } => Shape::Circle,
+        }
+    }
+
+    /// Rectangles and circles are placed by admins, only points come from regular users.
+    pub fn is_moderation(&self) -> bool {
+        !matches!(self, Coordinate::Point { .. })
+    }
+
+    /// Smallest and largest covered coordinates, both inclusive.
+    pub fn bounds(&self) -> ((i32, i32), (i32, i32)) {
+        match *self {
+            Coordinate::Point { x, y } => ((x, y), (x, y)),
+            Coordinate::Rectangle { x1, y1, x2, y2 } => ((x1, y1), (x2, y2)),
+            Coordinate::Circle { x, y, r } => {
+                let r = r as i32;
+
+                ((x - r, y - r), (x + r, y + r))
+            }
+        }
+    }
+
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        match *self {
+            Coordinate::Point {
+                x: point_x,
+                y: point_y,
+            } => x == point_x && y == point_y,
+            Coordinate::Rectangle { x1, y1, x2, y2 } => x >= x1 && x <= x2 && y >= y1 && y <= y2,
+            Coordinate::Circle {
+                x: center_x,
+                y: center_y,
+                r,
+            } => {
+                let (dx, dy) = (x - center_x, y - center_y);
+                let r = r as i32;
+
+                dx * dx + dy * dy <= r * r
+            }
+        }
+    }
+}
+
+impl fmt::Display for Coordinate {
+    /// Same format as in the dataset, without the quotes. Circles are written the way the
+    /// moderation records of the dataset write them, like `{X: 424, Y: 336, R: 8}`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Coordinate::Point { x, y } => write!(f, "{},{}", x, y),
+            Coordinate::Rectangle { x1, y1, x2, y2 } => write!(f, "{},{},{},{}", x1, y1, x2, y2),
+            Coordinate::Circle { x, y, r } => write!(f, "{{X: {}, Y: {}, R: {}}}", x, y, r),
+        }
+    }
+}
+
+/// Placement as it is replayed, with the user interned.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Record {
-    #[serde(deserialize_with = "deserialize_timestamp")]
     pub timestamp: NaiveDateTime,
-    pub user: String,
-    #[serde(deserialize_with = "deserialize_coordinate")]
+    pub user: UserId,
     pub coordinate: Coordinate,
-    #[serde(deserialize_with = "deserialize_color")]
     pub pixel_color: Rgb<u8>,
 }
 
+impl Record {
+    pub fn to_raw<'a>(&self, users: &'a UserInterner) -> RawRecord<'a> {
+        RawRecord {
+            timestamp: self.timestamp,
+            user: Cow::Borrowed(users.hash(self.user)),
+            coordinate: self.coordinate,
+            pixel_color: self.pixel_color,
+        }
+    }
+}
+
+/// Row of the dataset with the hash of the user spelled out. Serializes back into the format of
+/// the dataset, byte for byte.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RawRecord<'a> {
+    #[serde(
+        deserialize_with = "deserialize_timestamp",
+        serialize_with = "serialize_timestamp"
+    )]
+    pub timestamp: NaiveDateTime,
+    pub user: Cow<'a, str>,
+    #[serde(
+        deserialize_with = "deserialize_coordinate",
+        serialize_with = "serialize_coordinate"
+    )]
+    pub coordinate: Coordinate,
+    #[serde(
+        deserialize_with = "deserialize_color",
+        serialize_with = "hex_color::serialize"
+    )]
+    pub pixel_color: Rgb<u8>,
+}
+
+impl RawRecord<'_> {
+    pub fn intern(&self, users: &mut UserInterner) -> Record {
+        Record {
+            timestamp: self.timestamp,
+            user: users.intern(&self.user),
+            coordinate: self.coordinate,
+            pixel_color: self.pixel_color,
+        }
+    }
+}
+
 fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
 where
     D: Deserializer<'de>,
@@ -30,6 +147,30 @@ where
     NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S%.f %Z").map_err(serde::de::Error::custom)
 }
 
+/// Milliseconds are written without trailing zeros and left out when they are all zero, like
+/// `2023-07-20 13:00:26.7 UTC`.
+fn serialize_timestamp<S: Serializer>(
+    timestamp: &NaiveDateTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let milliseconds = timestamp.nanosecond() / 1_000_000;
+    let fraction = format!(".{:03}", milliseconds);
+    let fraction = fraction.trim_end_matches('0').trim_end_matches('.');
+
+    serializer.serialize_str(&format!(
+        "{}{} UTC",
+        timestamp.format("%Y-%m-%d %H:%M:%S"),
+        fraction
+    ))
+}
+
+fn serialize_coordinate<S: Serializer>(
+    coordinate: &Coordinate,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&coordinate.to_string())
+}
+
 fn deserialize_coordinate<'de, D>(deserializer: D) -> Result<Coordinate, D::Error>
 where
     D: Deserializer<'de>,
@@ -38,7 +179,7 @@ where
 
     let filtered_s: String = s
         .chars()
-        .filter(|&c| c.is_digit(10) || c == ',' || c == '-')
+        .filter(|&c| c.is_ascii_digit() || c == ',' || c == '-')
         .collect();
 
     let numbers: Vec<&str> = filtered_s.split(',').collect();
@@ -96,9 +237,16 @@ where
 {
     let s = String::deserialize(deserializer)?;
 
-    let r = u8::from_str_radix(&s[1..3], 16).map_err(serde::de::Error::custom)?;
-    let g = u8::from_str_radix(&s[3..5], 16).map_err(serde::de::Error::custom)?;
-    let b = u8::from_str_radix(&s[5..7], 16).map_err(serde::de::Error::custom)?;
+    let channel = |index: usize| {
+        let hex = s.get(index..index + 2).ok_or_else(|| {
+            serde::de::Error::custom(format!("Failed to parse color {:?}, expected #RRGGBB", s))
+        })?;
 
-    Ok(Rgb([r, g, b]))
+        u8::from_str_radix(hex, 16).map_err(serde::de::Error::custom)
+    };
+
+    Ok(Rgb([channel(1)?, channel(3)?, channel(5)?]))
 }
+
+/// Timestamp format used in outputs, always with milliseconds.
+pub(super) const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f UTC";
@@ -0,0 +1,331 @@
+use core::fmt;
+use std::str::from_utf8;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use csv::ByteRecord;
+use image::Rgb;
+
+use super::{
+    record::{Coordinate, Record},
+    user_interner::UserInterner,
+};
+
+#[derive(Debug)]
+pub enum RecordDecodeError {
+    MissingFields(usize),
+    InvalidUtf8(&'static str),
+    InvalidTimestamp(String, chrono::ParseError),
+    InvalidCoordinate(String),
+    InvalidColor(String),
+}
+
+impl fmt::Display for RecordDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordDecodeError::MissingFields(fields) => {
+                write!(f, "Expected 4 fields but got {}", fields)
+            }
+            RecordDecodeError::InvalidUtf8(field) => write!(f, "Invalid UTF-8 in the {}", field),
+            RecordDecodeError::InvalidTimestamp(timestamp, err) => {
+                write!(f, "Failed to parse timestamp {:?}: {}", timestamp, err)
+            }
+            RecordDecodeError::InvalidCoordinate(coordinate) => write!(
+                f,
+                "Failed to parse coordinate, expected (x,y), (x,y,r) or (x1,y1,x2,y2) but got {:?}",
+                coordinate
+            ),
+            RecordDecodeError::InvalidColor(color) => {
+                write!(f, "Failed to parse color {:?}, expected #RRGGBB", color)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecordDecodeError {}
+
+impl Record {
+    /// Decodes the fields straight from the bytes of the record, without going through serde.
+    /// Accepts and rejects the same records as deserializing does, only users seen for the first
+    /// time allocate.
+    pub fn from_byte_record(
+        record: &ByteRecord,
+        users: &mut UserInterner,
+    ) -> Result<Record, RecordDecodeError> {
+        if record.len() < 4 {
+            return Err(RecordDecodeError::MissingFields(record.len()));
+        }
+
+        let field = |index: usize, name: &'static str| {
+            from_utf8(&record[index]).map_err(|_| RecordDecodeError::InvalidUtf8(name))
+        };
+
+        let timestamp = field(0, "timestamp")?;
+        let user = field(1, "user")?;
+        let coordinate = field(2, "coordinate")?;
+        let color = field(3, "pixel color")?;
+
+        let timestamp = decode_timestamp(timestamp)?;
+        let coordinate = decode_coordinate(coordinate.as_bytes())
+            .ok_or_else(|| RecordDecodeError::InvalidCoordinate(coordinate.to_owned()))?;
+        let pixel_color = decode_color(color.as_bytes())
+            .ok_or_else(|| RecordDecodeError::InvalidColor(color.to_owned()))?;
+
+        // Interned last, so that users of invalid records don't end up in the table
+        Ok(Record {
+            timestamp,
+            user: users.intern(user),
+            coordinate,
+            pixel_color,
+        })
+    }
+}
+
+/// Reads the layout of the dataset, `2023-07-20 13:00:26.088 UTC` with an optional fraction,
+/// by position. Anything else goes through the same format string deserializing uses.
+fn decode_timestamp(s: &str) -> Result<NaiveDateTime, RecordDecodeError> {
+    if let Some(timestamp) = decode_fixed_timestamp(s.as_bytes()) {
+        return Ok(timestamp);
+    }
+
+    NaiveDateTime::parse_from_str(s, TIMESTAMP_PARSE_FORMAT)
+        .map_err(|err| RecordDecodeError::InvalidTimestamp(s.to_owned(), err))
+}
+
+fn decode_fixed_timestamp(bytes: &[u8]) -> Option<NaiveDateTime> {
+    let bytes = bytes.strip_suffix(b" UTC")?;
+
+    if bytes.len() < 19
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || bytes[10] != b' '
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+
+    let number = |range: std::ops::Range<usize>| {
+        bytes[range].iter().try_fold(0u32, |value, &byte| {
+            byte.is_ascii_digit()
+                .then(|| value * 10 + (byte - b'0') as u32)
+        })
+    };
+
+    let nanoseconds = match &bytes[19..] {
+        [] => 0,
+        [b'.', digits @ ..] if (1..=9).contains(&digits.len()) => {
+            number(20..bytes.len())? * 10u32.pow(9 - digits.len() as u32)
+        }
+        _ => return None,
+    };
+
+    NaiveDate::from_ymd_opt(number(0..4)? as i32, number(5..7)?, number(8..10)?)?.and_hms_nano_opt(
+        number(11..13)?,
+        number(14..16)?,
+        number(17..19)?,
+        nanoseconds,
+    )
+}
+
+/// Like deserializing, everything but digits, `,` and `-` is ignored and the numbers between
+/// the commas have to parse as `i32`, or `u32` for the radius.
+fn decode_coordinate(bytes: &[u8]) -> Option<Coordinate> {
+    let mut numbers = [CoordinateNumber::default(); 4];
+    let mut count = 1;
+
+    for &byte in bytes {
+        match byte {
+            b',' => {
+                if count == numbers.len() {
+                    return None;
+                }
+                count += 1;
+            }
+            b'-' | b'0'..=b'9' => numbers[count - 1].push(byte),
+            _ => {}
+        }
+    }
+
+    match numbers[..count] {
+        [x, y] => Some(Coordinate::Point {
+            x: x.signed()?,
+            y: y.signed()?,
+        }),
+        [x, y, r] => Some(Coordinate::Circle {
+            x: x.signed()?,
+            y: y.signed()?,
+            r: r.unsigned()?,
+        }),
+        [x1, y1, x2, y2] => Some(Coordinate::Rectangle {
+            x1: x1.signed()?,
+            y1: y1.signed()?,
+            x2: x2.signed()?,
+            y2: y2.signed()?,
+        }),
+        _ => None,
+    }
+}
+
+/// Digits and minus signs between two commas, checked the way `str::parse` checks them.
+#[derive(Clone, Copy, Default)]
+struct CoordinateNumber {
+    magnitude: i64,
+    length: u32,
+    digits: u32,
+    negative: bool,
+    misplaced_sign: bool,
+}
+
+impl CoordinateNumber {
+    fn push(&mut self, byte: u8) {
+        if byte == b'-' {
+            if self.length == 0 {
+                self.negative = true;
+            } else {
+                self.misplaced_sign = true;
+            }
+        } else {
+            // Capped so that long runs of digits can't overflow, they are out of range anyway
+            self.magnitude = (self.magnitude * 10 + (byte - b'0') as i64).min(1 << 40);
+            self.digits += 1;
+        }
+
+        self.length += 1;
+    }
+
+    fn signed(self) -> Option<i32> {
+        if self.digits == 0 || self.misplaced_sign {
+            return None;
+        }
+
+        let value = if self.negative {
+            -self.magnitude
+        } else {
+            self.magnitude
+        };
+
+        i32::try_from(value).ok()
+    }
+
+    fn unsigned(self) -> Option<u32> {
+        if self.digits == 0 || self.misplaced_sign || self.negative {
+            return None;
+        }
+
+        u32::try_from(self.magnitude).ok()
+    }
+}
+
+/// Like deserializing, only the three channels after the first byte are read and a channel may
+/// be a `+` followed by a single hex digit.
+fn decode_color(bytes: &[u8]) -> Option<Rgb<u8>> {
+    let channel = |index: usize| {
+        let hex_digit = |byte: u8| (byte as char).to_digit(16).map(|digit| digit as u8);
+
+        match bytes.get(index..index + 2)? {
+            [b'+', low] => hex_digit(*low),
+            [high, low] => Some(hex_digit(*high)? * 16 + hex_digit(*low)?),
+            _ => None,
+        }
+    };
+
+    Some(Rgb([channel(1)?, channel(3)?, channel(5)?]))
+}
+
+const TIMESTAMP_PARSE_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f %Z";
+
+#[cfg(test)]
+mod tests {
+    use csv::{ByteRecord, ReaderBuilder};
+
+    use crate::rplace_data_parser::{RawRecord, Record, UserInterner};
+
+    #[test]
+    fn test_decoder_agrees_with_deserializing() {
+        let mut paths: Vec<String> = (0..=5)
+            .map(|chunk| {
+                format!(
+                    "assets/rplace_data_sample/2023_place_canvas_history-{:012}.csv",
+                    chunk
+                )
+            })
+            .collect();
+        paths.push(String::from(
+            "assets/rplace_data_sample/different_forms_of_coordinates.csv",
+        ));
+        paths.push(String::from(
+            "assets/rplace_data_sample/off_diagonal_circles.csv",
+        ));
+        paths.push(String::from(
+            "assets/rplace_data_sample/moderation_shapes.csv",
+        ));
+
+        for path in paths {
+            let mut serde_reader = ReaderBuilder::new().from_path(&path).unwrap();
+            let mut byte_reader = ReaderBuilder::new().from_path(&path).unwrap();
+            let mut byte_record = ByteRecord::new();
+            let mut users = UserInterner::new();
+
+            for result in serde_reader.deserialize::<RawRecord>() {
+                assert!(byte_reader.read_byte_record(&mut byte_record).unwrap());
+
+                let decoded = Record::from_byte_record(&byte_record, &mut users).unwrap();
+
+                assert_eq!(result.unwrap().intern(&mut users), decoded);
+            }
+            assert!(!byte_reader.read_byte_record(&mut byte_record).unwrap());
+        }
+
+        let lines = [
+            "2023-07-20 13:00:26.088 UTC,u,\"1,2\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"-1,2,3,-4\",#0A1B2C",
+            "2023-07-20 13:00:26.088 UTC,u,\"1,2\",#FFFFFF,extra",
+            "2023-07-20 13:00:26.088 UTC,u,\"1,2\"",
+            "2023-7-20 13:00:26.088 UTC,u,\"1,2\",#FFFFFF",
+            "2023-07-20 13:00:26.088 XYZ,u,\"1,2\",#FFFFFF",
+            "2023-07-20 13:00:26.088,u,\"1,2\",#FFFFFF",
+            "2023-07-20 13:00:26. UTC,u,\"1,2\",#FFFFFF",
+            "2023-07-20 13:00:26.1234567891 UTC,u,\"1,2\",#FFFFFF",
+            "2023-07-20 13:00:60 UTC,u,\"1,2\",#FFFFFF",
+            "2023-02-30 13:00:26 UTC,u,\"1,2\",#FFFFFF",
+            "2023-07-20  13:00:26 UTC,u,\"1,2\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC ,u,\"1,2\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1a,2\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,-0,3\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2,-3\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2,+3\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2-,3\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,,3\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2,3,4,5\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"2147483648,0\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"-2147483648,0\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"0,0,4294967295\",#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,#FFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2\",#FFFFFFAA",
+            "2023-07-20 13:00:26 UTC,u,\"1,2\",#+FFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2\",#-FFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2\",#GGFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2\",FFFFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2\",#FFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2\",#FFFFF",
+            "2023-07-20 13:00:26 UTC,u,\"1,2\",#FFé00",
+            "2023-07-20 13:00:26 UTC,u,\"1,2\",",
+        ];
+
+        for line in lines {
+            let mut reader = ReaderBuilder::new()
+                .has_headers(false)
+                .from_reader(line.as_bytes());
+            let byte_record = reader.byte_records().next().unwrap().unwrap();
+            let mut users = UserInterner::new();
+            let serde = byte_record.deserialize::<RawRecord>(None);
+            let decoded = Record::from_byte_record(&byte_record, &mut users);
+
+            match (serde, decoded) {
+                (Ok(serde), Ok(decoded)) => assert_eq!(serde.intern(&mut users), decoded),
+                (Err(_), Err(_)) => {}
+                (serde, decoded) => panic!("{}: {:?} but {:?}", line, serde, decoded),
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::{fs::File, path::PathBuf};
+
+use anyhow::Result;
+use csv::Writer;
+
+use super::{
+    parser_image::ParserImage, record::Record, replay_observer::ReplayObserver,
+    user_interner::UserInterner,
+};
+
+/// Writes records as CSV in the format of the dataset. As a [`ReplayObserver`] it exports every
+/// record that passed the filters of the parser.
+pub struct RecordExporter {
+    writer: Writer<File>,
+    error: Option<csv::Error>,
+}
+
+impl RecordExporter {
+    pub fn create(path: &PathBuf) -> Result<RecordExporter> {
+        Ok(RecordExporter {
+            writer: Writer::from_path(path)?,
+            error: None,
+        })
+    }
+
+    /// Flushes the file, returning the first error that happened while exporting during a replay.
+    pub fn close(mut self) -> Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error.into());
+        }
+
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+impl ReplayObserver for RecordExporter {
+    fn after_record(&mut self, record: &Record, _canvas: &ParserImage, users: &UserInterner) {
+        if self.error.is_some() {
+            return;
+        }
+
+        if let Err(error) = self.writer.serialize(record.to_raw(users)) {
+            self.error = Some(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read;
+
+    use chrono::NaiveDateTime;
+    use csv::{Reader, Writer};
+
+    use crate::rplace_data_parser::{Coordinate, RawRecord};
+
+    #[test]
+    fn test_serialize_records_byte_for_byte() {
+        let mut paths: Vec<String> = (0..=5)
+            .map(|chunk| {
+                format!(
+                    "assets/rplace_data_sample/2023_place_canvas_history-{:012}.csv",
+                    chunk
+                )
+            })
+            .collect();
+        paths.push(String::from(
+            "assets/rplace_data_sample/moderation_shapes.csv",
+        ));
+
+        for path in paths {
+            let original = read(&path).unwrap();
+
+            let mut reader = Reader::from_path(&path).unwrap();
+            let mut writer = Writer::from_writer(vec![]);
+
+            for result in reader.deserialize() {
+                let record: RawRecord = result.unwrap();
+                writer.serialize(record).unwrap();
+            }
+
+            let written = writer.into_inner().unwrap();
+
+            assert!(written == original, "{} differs after a round trip", path);
+        }
+
+        let mut writer = Writer::from_writer(vec![]);
+        writer
+            .serialize(RawRecord {
+                timestamp: NaiveDateTime::parse_from_str(
+                    "2023-07-20 13:00:26",
+                    "%Y-%m-%d %H:%M:%S",
+                )
+                .unwrap(),
+                user: "user".into(),
+                coordinate: Coordinate::Circle { x: -5, y: 3, r: 12 },
+                pixel_color: image::Rgb([255, 69, 0]),
+            })
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+            "timestamp,user,coordinate,pixel_color\n2023-07-20 13:00:26 UTC,user,\"{X: -5, Y: 3, R: 12}\",#FF4500\n"
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::{collections::HashSet, fs::read_to_string};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use image::Rgb;
+use serde::{Deserialize, Serialize};
+
+use crate::config_file::{optional_hex_colors, optional_timestamp};
+
+use super::{
+    record::{Record, Shape},
+    user_interner::{UserId, UserInterner},
+};
+
+/// Decides whether a record is replayed at all. Records have to pass every filter. Filters are
+/// applied while the files are parsed in parallel, `users` resolves the user of the record and
+/// `decisions` belongs to the filter for the piece being parsed.
+pub trait RecordFilter: Send + Sync {
+    fn accepts(&self, record: &Record, users: &UserInterner, decisions: &mut UserDecisions)
+        -> bool;
+}
+
+/// Lets filters that only look at the user decide once per user of a piece, indexed by the ids
+/// of the [`UserInterner`] of that piece.
+#[derive(Debug, Default)]
+pub struct UserDecisions(Vec<Option<bool>>);
+
+impl UserDecisions {
+    pub fn new() -> UserDecisions {
+        UserDecisions::default()
+    }
+
+    /// Returns the earlier decision for the user, or makes and keeps it the first time.
+    pub fn get_or_decide(&mut self, user: UserId, decide: impl FnOnce() -> bool) -> bool {
+        let index = user.0 as usize;
+
+        if index >= self.0.len() {
+            self.0.resize(index + 1, None);
+        }
+
+        *self.0[index].get_or_insert_with(decide)
+    }
+}
+
+/// Both ends inclusive.
+pub struct TimeRangeFilter {
+    pub start: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+}
+
+impl RecordFilter for TimeRangeFilter {
+    fn accepts(
+        &self,
+        record: &Record,
+        _users: &UserInterner,
+        _decisions: &mut UserDecisions,
+    ) -> bool {
+        self.start.is_none_or(|start| record.timestamp >= start)
+            && self.end.is_none_or(|end| record.timestamp <= end)
+    }
+}
+
+/// Without an allow list every user not on the deny list is accepted.
+pub struct UserFilter {
+    pub allowed: Option<HashSet<String>>,
+    pub denied: HashSet<String>,
+}
+
+impl RecordFilter for UserFilter {
+    fn accepts(
+        &self,
+        record: &Record,
+        users: &UserInterner,
+        decisions: &mut UserDecisions,
+    ) -> bool {
+        decisions.get_or_decide(record.user, || {
+            let user = users.hash(record.user);
+
+            self.allowed
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(user))
+                && !self.denied.contains(user)
+        })
+    }
+}
+
+/// Accepts records touching the area between the corners, both inclusive.
+pub struct BoundingBoxFilter {
+    pub min: (i32, i32),
+    pub max: (i32, i32),
+}
+
+impl RecordFilter for BoundingBoxFilter {
+    fn accepts(
+        &self,
+        record: &Record,
+        _users: &UserInterner,
+        _decisions: &mut UserDecisions,
+    ) -> bool {
+        let ((min_x, min_y), (max_x, max_y)) = record.coordinate.bounds();
+
+        min_x <= self.max.0 && max_x >= self.min.0 && min_y <= self.max.1 && max_y >= self.min.1
+    }
+}
+
+pub struct ColorFilter {
+    pub colors: HashSet<Rgb<u8>>,
+}
+
+impl RecordFilter for ColorFilter {
+    fn accepts(
+        &self,
+        record: &Record,
+        _users: &UserInterner,
+        _decisions: &mut UserDecisions,
+    ) -> bool {
+        self.colors.contains(&record.pixel_color)
+    }
+}
+
+pub struct ShapeFilter {
+    pub shapes: Vec<Shape>,
+}
+
+impl RecordFilter for ShapeFilter {
+    fn accepts(
+        &self,
+        record: &Record,
+        _users: &UserInterner,
+        _decisions: &mut UserDecisions,
+    ) -> bool {
+        self.shapes.contains(&record.coordinate.shape())
+    }
+}
+
+/// Filters of the parser config, every one left out accepts all records. Users can be listed
+/// directly or in files with one user hash per line.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    #[serde(with = "optional_timestamp")]
+    pub start: Option<NaiveDateTime>,
+    #[serde(with = "optional_timestamp")]
+    pub end: Option<NaiveDateTime>,
+    pub allowed_users: Option<Vec<String>>,
+    pub allowed_users_file: Option<String>,
+    pub denied_users: Vec<String>,
+    pub denied_users_file: Option<String>,
+    /// `[x1, y1, x2, y2]`
+    pub bounding_box: Option<[i32; 4]>,
+    #[serde(with = "optional_hex_colors")]
+    pub colors: Option<Vec<Rgb<u8>>>,
+    pub shapes: Option<Vec<Shape>>,
+}
+
+impl FilterConfig {
+    pub fn new_default() -> FilterConfig {
+        FilterConfig::default()
+    }
+
+    /// Builds the filters, reading the files of user hashes.
+    pub fn build(&self) -> Result<Vec<Box<dyn RecordFilter>>> {
+        let mut filters: Vec<Box<dyn RecordFilter>> = vec![];
+
+        if self.start.is_some() || self.end.is_some() {
+            filters.push(Box::new(TimeRangeFilter {
+                start: self.start,
+                end: self.end,
+            }));
+        }
+
+        let allowed = match (&self.allowed_users, &self.allowed_users_file) {
+            (None, None) => None,
+            (users, path) => {
+                let mut allowed: HashSet<String> = users.iter().flatten().cloned().collect();
+                if let Some(path) = path {
+                    allowed.extend(read_users(path)?);
+                }
+                Some(allowed)
+            }
+        };
+
+        let mut denied: HashSet<String> = self.denied_users.iter().cloned().collect();
+        if let Some(path) = &self.denied_users_file {
+            denied.extend(read_users(path)?);
+        }
+
+        if allowed.is_some() || !denied.is_empty() {
+            filters.push(Box::new(UserFilter { allowed, denied }));
+        }
+
+        if let Some([x1, y1, x2, y2]) = self.bounding_box {
+            filters.push(Box::new(BoundingBoxFilter {
+                min: (x1, y1),
+                max: (x2, y2),
+            }));
+        }
+
+        if let Some(colors) = &self.colors {
+            filters.push(Box::new(ColorFilter {
+                colors: colors.iter().copied().collect(),
+            }));
+        }
+
+        if let Some(shapes) = &self.shapes {
+            filters.push(Box::new(ShapeFilter {
+                shapes: shapes.clone(),
+            }));
+        }
+
+        Ok(filters)
+    }
+}
+
+fn read_users(path: &str) -> Result<Vec<String>> {
+    let content =
+        read_to_string(path).with_context(|| format!("Failed to read user list {}", path))?;
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use csv::Reader;
+
+    use crate::rplace_data_parser::{RawRecord, Record, UserId, UserInterner};
+
+    use super::{FilterConfig, UserDecisions};
+
+    fn accepted(filter_config: &str) -> usize {
+        let filter_config: FilterConfig = toml::from_str(filter_config).unwrap();
+        let filters = filter_config.build().unwrap();
+
+        let mut reader =
+            Reader::from_path("assets/rplace_data_sample/different_forms_of_coordinates.csv")
+                .unwrap();
+        let mut users = UserInterner::new();
+
+        let records: Vec<Record> = reader
+            .deserialize()
+            .map(|result: csv::Result<RawRecord>| result.unwrap().intern(&mut users))
+            .collect();
+
+        let mut decisions: Vec<UserDecisions> =
+            filters.iter().map(|_| UserDecisions::new()).collect();
+
+        records
+            .iter()
+            .filter(|record| {
+                filters
+                    .iter()
+                    .zip(&mut decisions)
+                    .all(|(filter, decisions)| filter.accepts(record, &users, decisions))
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_record_filters() {
+        assert_eq!(accepted(""), 3);
+        assert_eq!(accepted("start = \"2023-07-20 13:00:40\""), 2);
+        assert_eq!(accepted("end = \"2023-07-20 13:00:43.658\""), 2);
+        assert_eq!(accepted("shapes = [\"rectangle\", \"circle\"]"), 2);
+        assert_eq!(accepted("colors = [\"#FFFFFF\"]"), 2);
+        assert_eq!(accepted("bounding_box = [0, 0, 10, 10]"), 1);
+        assert_eq!(
+            accepted("denied_users = [\"no+8HEIDjbdx7/LxH9Xr+h4lyoar0MRTYugWKrGdQOg7dFg0rU9STehlIqsje1kc48U/BQqB/0J8sHQzXJBDFA==\"]"),
+            2
+        );
+        assert_eq!(
+            accepted("allowed_users = []\nshapes = [\"point\"]\ncolors = [\"#FFFFFF\"]"),
+            0
+        );
+        assert!(toml::from_str::<FilterConfig>("colors = [\"red\"]").is_err());
+
+        let mut decisions = UserDecisions::new();
+
+        assert!(decisions.get_or_decide(UserId(2), || true));
+        assert!(decisions.get_or_decide(UserId(2), || false));
+        assert!(!decisions.get_or_decide(UserId(0), || false));
+    }
+}
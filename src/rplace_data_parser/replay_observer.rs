@@ -0,0 +1,13 @@
+use super::{parser_image::ParserImage, record::Record, user_interner::UserInterner};
+
+/// Gets to look at every record while the parser replays the history, together with the canvas
+/// right before and right after the record is drawn. `users` resolves the user ids of the
+/// records seen so far.
+pub trait ReplayObserver {
+    fn before_record(&mut self, _record: &Record, _canvas: &ParserImage, _users: &UserInterner) {}
+
+    fn after_record(&mut self, _record: &Record, _canvas: &ParserImage, _users: &UserInterner) {}
+
+    /// Called once after the last record, with the final canvas.
+    fn finish(&mut self, _canvas: &ParserImage, _users: &UserInterner) {}
+}
@@ -0,0 +1,168 @@
+use core::fmt;
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use csv::{Reader, Writer};
+use serde::{Deserialize, Serialize};
+
+/// Compact stand-in for the hash of a user, only meaningful together with the
+/// [`UserInterner`] that handed it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UserId(pub u32);
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug)]
+pub enum UserInternerError {
+    UnexpectedId { expected: u32, found: u32 },
+    DuplicateHash(String),
+}
+
+impl fmt::Display for UserInternerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserInternerError::UnexpectedId { expected, found } => {
+                write!(f, "Expected user id {} but got {}", expected, found)
+            }
+            UserInternerError::DuplicateHash(hash) => {
+                write!(f, "User hash {:?} appears more than once", hash)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UserInternerError {}
+
+#[derive(Deserialize, Serialize)]
+struct UserRow<'a> {
+    id: u32,
+    user: &'a str,
+}
+
+/// Hands out ids to user hashes in the order they are first seen, so that every hash is stored
+/// only once.
+#[derive(Default)]
+pub struct UserInterner {
+    ids: HashMap<Arc<str>, UserId>,
+    hashes: Vec<Arc<str>>,
+}
+
+impl UserInterner {
+    pub fn new() -> UserInterner {
+        UserInterner::default()
+    }
+
+    pub fn intern(&mut self, hash: &str) -> UserId {
+        if let Some(&id) = self.ids.get(hash) {
+            return id;
+        }
+
+        let id = UserId(self.hashes.len() as u32);
+        let hash: Arc<str> = hash.into();
+
+        self.hashes.push(hash.clone());
+        self.ids.insert(hash, id);
+
+        id
+    }
+
+    pub fn get(&self, hash: &str) -> Option<UserId> {
+        self.ids.get(hash).copied()
+    }
+
+    /// Panics on ids that weren't handed out by this interner.
+    pub fn hash(&self, id: UserId) -> &str {
+        &self.hashes[id.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Writes the table as CSV with an `id` and a `user` column, ordered by id.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut writer = Writer::from_path(path)?;
+
+        for (id, user) in self.hashes.iter().enumerate() {
+            writer.serialize(UserRow {
+                id: id as u32,
+                user,
+            })?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<UserInterner> {
+        let mut reader = Reader::from_path(path)?;
+        let mut interner = UserInterner::new();
+
+        for result in reader.records() {
+            let record = result?;
+            let row: UserRow = record.deserialize(None)?;
+
+            if row.id as usize != interner.len() {
+                return Err(anyhow!(UserInternerError::UnexpectedId {
+                    expected: interner.len() as u32,
+                    found: row.id,
+                }));
+            }
+            if interner.get(row.user).is_some() {
+                return Err(anyhow!(UserInternerError::DuplicateHash(
+                    row.user.to_string()
+                )));
+            }
+
+            interner.intern(row.user);
+        }
+
+        Ok(interner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{remove_file, write};
+
+    use super::{UserId, UserInterner};
+
+    #[test]
+    fn test_intern_and_persist_users() {
+        let mut users = UserInterner::new();
+
+        assert_eq!(users.intern("a"), UserId(0));
+        assert_eq!(users.intern("b"), UserId(1));
+        assert_eq!(users.intern("a"), UserId(0));
+        assert_eq!(users.hash(UserId(1)), "b");
+        assert_eq!(users.get("c"), None);
+        assert_eq!(users.intern("c"), UserId(2));
+
+        let path =
+            std::env::temp_dir().join(format!("pixel_crab_test_users_{}.csv", std::process::id()));
+        users.save(&path).unwrap();
+
+        let loaded = UserInterner::load(&path).unwrap();
+
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.get("c"), Some(UserId(2)));
+        assert_eq!(loaded.hash(UserId(1)), "b");
+
+        write(&path, "id,user\n0,a\n2,b\n").unwrap();
+        assert!(UserInterner::load(&path).is_err());
+
+        write(&path, "id,user\n0,a\n1,a\n").unwrap();
+        assert!(UserInterner::load(&path).is_err());
+
+        remove_file(&path).unwrap();
+    }
+}